@@ -1,15 +1,15 @@
 use base85::*;
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use rand::RngCore;
 
 fn encode_benchmark(c: &mut Criterion) {
     let mut testdata = [0; 0x100000];
     rand::thread_rng().fill_bytes(&mut testdata);
-    let encoded = encode(&testdata);
+    let encoded = encode(&testdata[..]);
 
     c.bench_function("encoder", |b| {
         b.iter(|| {
-            let _ = encode(black_box(&testdata));
+            let _ = encode(black_box(&testdata[..]));
         })
     });
 
@@ -20,5 +20,133 @@ fn encode_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, encode_benchmark);
+// Compares input sizes so that fixed per-call costs and the vectorized bulk path (with the
+// `simd` feature) can be told apart
+fn encode_sizes_benchmark(c: &mut Criterion) {
+    let mut testdata = vec![0; 0x100000];
+    rand::thread_rng().fill_bytes(&mut testdata);
+
+    let mut group = c.benchmark_group("encoder sizes");
+    for size in [64, 0x1000, 0x10000, 0x100000] {
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| {
+                let _ = encode(black_box(&testdata[..size]));
+            })
+        });
+    }
+    group.finish();
+}
+
+// The same comparison for decoding, where the `simd` feature decodes large valid inputs in bulk
+fn decode_sizes_benchmark(c: &mut Criterion) {
+    let mut testdata = vec![0; 0x100000];
+    rand::thread_rng().fill_bytes(&mut testdata);
+    let encoded = encode(&testdata);
+
+    let mut group = c.benchmark_group("decoder sizes");
+    for size in [64, 0x1000, 0x10000, 0x100000] {
+        let encoded = &encoded[..encoded_len(size)];
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), encoded, |b, encoded| {
+            b.iter(|| {
+                let _ = decode(black_box(encoded));
+            })
+        });
+    }
+    group.finish();
+}
+
+// The two ways of splitting a group into digits that encoding has used, dividing by each place
+// value in turn and multiplying a binary fraction up by 85, across input sizes
+fn group_digits_benchmark(c: &mut Criterion) {
+    fn divided(decnum: u32) -> [u8; 5] {
+        let mut digits = [0u8; 5];
+        let mut remainder = decnum;
+        for (i, digit) in digits.iter_mut().enumerate() {
+            let place = 85u32.pow(4 - i as u32);
+            *digit = (remainder / place) as u8;
+            remainder %= place;
+        }
+        digits
+    }
+
+    fn multiplied(decnum: u32) -> [u8; 5] {
+        const INV_POW85_4: u64 = ((1u128 << 64).div_ceil(85 * 85 * 85 * 85)) as u64;
+        let mut digits = [0u8; 5];
+        let scaled = decnum as u128 * INV_POW85_4 as u128;
+        digits[0] = (scaled >> 64) as u8;
+        let mut fraction = scaled as u64;
+        for digit in &mut digits[1..] {
+            let scaled = fraction as u128 * 85;
+            *digit = (scaled >> 64) as u8;
+            fraction = scaled as u64;
+        }
+        digits
+    }
+
+    let mut testdata = vec![0; 0x100000];
+    rand::thread_rng().fill_bytes(&mut testdata);
+
+    let mut group = c.benchmark_group("group digits");
+    for size in [64, 0x1000, 0x100000] {
+        group.throughput(Throughput::Bytes(size as u64));
+        for (name, digits) in [
+            ("division", divided as fn(u32) -> [u8; 5]),
+            ("multiplication", multiplied),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, size), &size, |b, &size| {
+                b.iter(|| {
+                    let mut outdata = Vec::with_capacity(size / 4 * 5);
+                    for chunk in black_box(&testdata[..size]).chunks_exact(4) {
+                        let decnum = u32::from_be_bytes(chunk.try_into().unwrap());
+                        outdata.extend_from_slice(&digits(decnum));
+                    }
+                    outdata
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+// Compares the serial and parallel encoders on a large input to show how encoding scales across
+// threads
+#[cfg(feature = "rayon")]
+fn encode_parallel_benchmark(c: &mut Criterion) {
+    let mut testdata = vec![0; 0x1000000];
+    rand::thread_rng().fill_bytes(&mut testdata);
+
+    let mut group = c.benchmark_group("parallel encoder");
+    group.throughput(Throughput::Bytes(testdata.len() as u64));
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            let _ = encode(black_box(&testdata[..]));
+        })
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| {
+            let _ = encode_parallel(black_box(&testdata[..]));
+        })
+    });
+    group.finish();
+}
+
+#[cfg(not(feature = "rayon"))]
+criterion_group!(
+    benches,
+    encode_benchmark,
+    encode_sizes_benchmark,
+    decode_sizes_benchmark,
+    group_digits_benchmark
+);
+#[cfg(feature = "rayon")]
+criterion_group!(
+    benches,
+    encode_benchmark,
+    encode_sizes_benchmark,
+    decode_sizes_benchmark,
+    group_digits_benchmark,
+    encode_parallel_benchmark
+);
 criterion_main!(benches);
@@ -0,0 +1,36 @@
+//! Decoding into fixed-capacity stack buffers
+
+use arrayvec::ArrayVec;
+
+use crate::{decode_groups, DecodeOptions, Error, Result};
+
+/// decode_arrayvec() decodes data into a fixed-capacity stack buffer, an ArrayVec holding up to
+/// `N` bytes. If the data decodes to more than `N` bytes, `Error::BufferTooSmall` is returned.
+pub fn decode_arrayvec<const N: usize, T: AsRef<[u8]>>(input: T) -> Result<ArrayVec<u8, N>> {
+    let mut outdata = ArrayVec::new();
+    decode_groups(input.as_ref(), &DecodeOptions::default(), |bytes| {
+        outdata
+            .try_extend_from_slice(bytes)
+            .map_err(|_| Error::BufferTooSmall)
+    })?;
+    Ok(outdata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_arrayvec() {
+        let decoded = decode_arrayvec::<5, _>("VPRom VE").unwrap();
+        assert_eq!(decoded.as_slice(), b"aaaaa");
+        assert_eq!(
+            decode_arrayvec::<4, _>("VPRomVE"),
+            Err(Error::BufferTooSmall)
+        );
+        assert_eq!(
+            decode_arrayvec::<16, _>("VPRomV"),
+            Err(Error::InvalidLength(6))
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! The Adobe variant of Base85, better known as ascii85, as used by PostScript and PDF.
+//!
+//! This is available only with the `ascii85` feature. Ascii85 uses the characters `!` through
+//! `u` for the digits 0 to 84 and adds a shortcut: a group of four zero bytes is written as `z`.
+//! The btoa shortcut of `y` for a group of four spaces isn't part of Adobe's format, so it's
+//! accepted when decoding but never written. Encoded data is wrapped in `<~` and `~>`
+//! delimiters, which are optional when decoding. Data which starts with `<~` has to end
+//! with `~>`, though, so that data which was cut short is caught.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{group_digits, is_whitespace, Error, GroupDecoder, Result};
+
+const FIRST_CHAR: u8 = b'!';
+const LAST_CHAR: u8 = b'u';
+
+// Adobe counts NUL and form feed as whitespace too
+#[inline]
+const fn is_ascii85_whitespace(c: u8) -> bool {
+    is_whitespace(c) || matches!(c, b'\0' | 0x0c)
+}
+
+/// encode() turns a slice of bytes into a string of ascii85-encoded data, including the `<~`
+/// and `~>` delimiters
+pub fn encode(indata: &[u8]) -> String {
+    let mut outdata = String::with_capacity(crate::encoded_len(indata.len()) + 4);
+    outdata.push_str("<~");
+
+    let mut chunks = indata.chunks_exact(4);
+    for chunk in chunks.by_ref() {
+        let decnum = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        if decnum == 0 {
+            outdata.push('z');
+        } else {
+            for digit in group_digits(decnum) {
+                outdata.push((digit + FIRST_CHAR) as char);
+            }
+        }
+    }
+
+    // The shortcut only stands in for full groups, so the tail is always spelled out
+    let extra = chunks.remainder();
+    if !extra.is_empty() {
+        let mut last_chunk = [0u8; 4];
+        last_chunk[..extra.len()].copy_from_slice(extra);
+        let digits = group_digits(u32::from_be_bytes(last_chunk));
+        for &digit in &digits[..extra.len() + 1] {
+            outdata.push((digit + FIRST_CHAR) as char);
+        }
+    }
+
+    outdata.push_str("~>");
+    outdata
+}
+
+/// decode() turns ascii85-encoded data into a vector of bytes. The `<~` and `~>` delimiters are
+/// stripped if present and whitespace, including NUL and form feed, is ignored. If the data
+/// starts with `<~` but doesn't end with `~>`, `Error::UnexpectedEof` is returned.
+pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    let mut indata = input.as_ref();
+    let input_len = indata.len();
+    // Offsets in errors are relative to the original input
+    let mut start = 0;
+    if let Some(stripped) = indata.strip_prefix(b"<~") {
+        indata = stripped;
+        start = 2;
+    }
+    let mut terminated = start == 0;
+    if let Some(stripped) = indata.strip_suffix(b"~>") {
+        indata = stripped;
+        terminated = true;
+    }
+
+    let mut outdata = Vec::with_capacity(crate::decoded_len(indata.len()));
+    let mut group = GroupDecoder::default();
+    for (i, &c) in indata.iter().enumerate() {
+        let offset = start + i;
+        match c {
+            c if is_ascii85_whitespace(c) => {}
+            b'z' | b'y' => {
+                // A shortcut can't appear in the middle of a group
+                if group.count != 0 {
+                    return Err(Error::InvalidCharacter { byte: c, offset });
+                }
+                let fill = if c == b'z' { 0 } else { b' ' };
+                outdata.extend_from_slice(&[fill; 4]);
+            }
+            FIRST_CHAR..=LAST_CHAR => {
+                if let Some(bytes) = group.push(c - FIRST_CHAR, offset)? {
+                    outdata.extend_from_slice(&bytes);
+                }
+            }
+            _ => return Err(Error::InvalidCharacter { byte: c, offset }),
+        }
+    }
+
+    if !terminated {
+        return Err(Error::UnexpectedEof {
+            offset: input_len,
+            in_group: group.count != 0,
+        });
+    }
+
+    let (bytes, len) = group.finish()?;
+    outdata.extend_from_slice(&bytes[..len]);
+
+    Ok(outdata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii85() {
+        let testlist: [(&[u8], &str); 6] = [
+            (b"", "<~~>"),
+            (b"h", "<~BE~>"),
+            (b"hello", "<~BOu!rDZ~>"),
+            (b"Man sure.", "<~9jqo^F*2M7/c~>"),
+            (&[0, 0, 0, 0, 0], "<~z!!~>"),
+            (b"    ", "<~+<VdL~>"),
+        ];
+
+        for (data, encoded) in testlist {
+            assert_eq!(encode(data), encoded);
+            assert_eq!(decode(encoded).unwrap(), data);
+        }
+
+        assert_eq!(decode("9jqo^ F*2M\n7/c").unwrap(), b"Man sure.");
+        assert_eq!(decode("9jqo^\0F*2M\x0c7/c").unwrap(), b"Man sure.");
+        assert_eq!(decode("<~y~>").unwrap(), b"    ");
+        assert!(matches!(
+            decode("<~9jzqo~>"),
+            Err(Error::InvalidCharacter {
+                byte: b'z',
+                offset: 4
+            })
+        ));
+        assert!(matches!(decode("<~9jqo^F~>"), Err(Error::InvalidLength(6))));
+
+        // Data cut short between groups and partway through one
+        assert!(matches!(
+            decode("<~9jqo^"),
+            Err(Error::UnexpectedEof {
+                offset: 7,
+                in_group: false
+            })
+        ));
+        assert!(matches!(
+            decode("<~9jqo^F*"),
+            Err(Error::UnexpectedEof {
+                offset: 9,
+                in_group: true
+            })
+        ));
+
+        // Whitespace before the end counts towards the offset but doesn't start a group
+        assert!(matches!(
+            decode("<~9jqo^\n"),
+            Err(Error::UnexpectedEof {
+                offset: 8,
+                in_group: false
+            })
+        ));
+        assert!(matches!(
+            decode("<~9jqo^ F*\r\n"),
+            Err(Error::UnexpectedEof {
+                offset: 12,
+                in_group: true
+            })
+        ));
+        assert_eq!(
+            decode("<~9jqo^").unwrap_err().to_string(),
+            "Unexpected end of input at offset 7"
+        );
+        assert!(matches!(
+            decode("<~9jqo^v~>"),
+            Err(Error::InvalidCharacter {
+                byte: b'v',
+                offset: 7
+            })
+        ));
+    }
+}
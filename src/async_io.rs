@@ -0,0 +1,227 @@
+//! Adapters for encoding and decoding data as it passes through tokio's async readers and writers
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{decoded_len, Base85Decoder, Base85Encoder};
+
+// The number of groups encoded before they are handed to the inner writer
+const GROUPS_PER_WRITE: usize = 1024;
+
+// The number of bytes read from an inner reader at a time
+const READ_BUFFER_LEN: usize = 4096;
+
+/// AsyncEncoder wraps an async writer and encodes everything written to it before passing it
+/// along, like `Encoder`. The last 1 to 3 bytes are held back until the writer is shut down, so
+/// `shutdown()` has to be called to write them.
+pub struct AsyncEncoder<W: AsyncWrite + Unpin> {
+    inner: W,
+    encoder: Base85Encoder,
+    // Encoded data which the inner writer hasn't taken yet
+    outbuf: String,
+    out_pos: usize,
+    finished: bool,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncoder<W> {
+    /// new() creates an AsyncEncoder which writes encoded data to `inner`
+    pub fn new(inner: W) -> AsyncEncoder<W> {
+        AsyncEncoder {
+            inner,
+            encoder: Base85Encoder::new(),
+            outbuf: String::with_capacity(GROUPS_PER_WRITE * 5),
+            out_pos: 0,
+            finished: false,
+        }
+    }
+
+    /// into_inner() returns the inner writer. Bytes which haven't been written yet are lost.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.out_pos < self.outbuf.len() {
+            let outdata = &self.outbuf.as_bytes()[self.out_pos..];
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, outdata))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.out_pos += n;
+        }
+        self.outbuf.clear();
+        self.out_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncEncoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // Encoded data from the last call has to go out before more is accepted
+        ready!(this.poll_drain(cx))?;
+
+        // Take no more than fits in one batch of groups
+        let len = buf.len().min(GROUPS_PER_WRITE * 4);
+        this.encoder.update_into(&buf[..len], &mut this.outbuf);
+
+        // Start the write now, but the data is ours to write whether or not it's done yet, and if
+        // it fails the error is left for the next call, which tries again
+        let _ = this.poll_drain(cx);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            ready!(this.poll_drain(cx))?;
+            this.encoder.finish_into(&mut this.outbuf);
+            this.finished = true;
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// AsyncDecoder wraps an async reader of encoded data and yields the decoded bytes, like
+/// `Decoder`. Whitespace is skipped and problems with the encoded data are reported as
+/// `io::ErrorKind::InvalidData` errors wrapping the crate's `Error`, except for data which ends
+/// partway through a group, which is an `io::ErrorKind::UnexpectedEof` error.
+pub struct AsyncDecoder<R: AsyncRead + Unpin> {
+    inner: R,
+    decoder: Base85Decoder,
+    inbuf: Vec<u8>,
+    // Bytes decoded from the last read of the inner reader which haven't been handed over yet
+    outbuf: Vec<u8>,
+    out_pos: usize,
+    finished: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecoder<R> {
+    /// new() creates an AsyncDecoder which reads encoded data from `inner`
+    pub fn new(inner: R) -> AsyncDecoder<R> {
+        AsyncDecoder {
+            inner,
+            decoder: Base85Decoder::new(),
+            inbuf: vec![0; READ_BUFFER_LEN],
+            outbuf: Vec::with_capacity(decoded_len(READ_BUFFER_LEN) + 4),
+            out_pos: 0,
+            finished: false,
+        }
+    }
+
+    /// into_inner() returns the inner reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        // Only go back to the inner reader once everything decoded so far has been handed over
+        while this.out_pos == this.outbuf.len() {
+            if this.finished {
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut inbuf = ReadBuf::new(&mut this.inbuf);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut inbuf))?;
+            let n = inbuf.filled().len();
+            this.outbuf.clear();
+            this.out_pos = 0;
+            if n == 0 {
+                this.finished = true;
+                this.decoder.finish_into(&mut this.outbuf)?;
+            } else {
+                this.decoder
+                    .update_into(&this.inbuf[..n], &mut this.outbuf)?;
+            }
+        }
+
+        let n = (this.outbuf.len() - this.out_pos).min(buf.remaining());
+        buf.put_slice(&this.outbuf[this.out_pos..this.out_pos + n]);
+        this.out_pos += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode, Error};
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_async_roundtrip() {
+        let indata: Vec<u8> = (0..10003u32).map(|i| (i * 7) as u8).collect();
+
+        // A small duplex buffer forces both sides through many partial reads and writes
+        let (client, server) = duplex(7);
+        let writer = {
+            let indata = indata.clone();
+            async move {
+                let mut encoder = AsyncEncoder::new(client);
+                for chunk in indata.chunks(3) {
+                    encoder.write_all(chunk).await.unwrap();
+                }
+                encoder.shutdown().await.unwrap();
+            }
+        };
+        let reader = async move {
+            let mut decoder = AsyncDecoder::new(server);
+            let mut outdata = Vec::new();
+            let mut buf = [0u8; 5];
+            loop {
+                let n = decoder.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                outdata.extend_from_slice(&buf[..n]);
+            }
+            outdata
+        };
+        let ((), outdata) = tokio::join!(writer, reader);
+        assert_eq!(outdata, indata);
+
+        let mut encoded = Vec::new();
+        let mut encoder = AsyncEncoder::new(&mut encoded);
+        encoder.write_all(b"aaaaa").await.unwrap();
+        encoder.shutdown().await.unwrap();
+        assert_eq!(encoded, encode(b"aaaaa").as_bytes());
+
+        let err = AsyncDecoder::new(&b"VPRomV"[..])
+            .read_to_end(&mut Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(&Error::UnexpectedEof {
+                offset: 6,
+                in_group: true
+            })
+        );
+    }
+}
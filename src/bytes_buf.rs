@@ -0,0 +1,38 @@
+//! Encoding from and decoding into the buffers of the bytes crate
+
+use alloc::string::String;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{decode_groups, decoded_len, encode, DecodeOptions, Result};
+
+/// encode_bytes() encodes the contents of a `Bytes` buffer
+pub fn encode_bytes(buf: &Bytes) -> String {
+    encode(buf)
+}
+
+/// decode_to_bytes() decodes data into a `Bytes` buffer, which is allocated once at the size
+/// given by `decoded_len()`
+pub fn decode_to_bytes<T: AsRef<[u8]>>(input: T) -> Result<Bytes> {
+    let indata = input.as_ref();
+    let mut outdata = BytesMut::with_capacity(decoded_len(indata.len()));
+    decode_groups(indata, &DecodeOptions::default(), |bytes| {
+        outdata.extend_from_slice(bytes);
+        Ok(())
+    })?;
+    Ok(outdata.freeze())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes() {
+        let buf = Bytes::from_static(b"aaaaa");
+        let encoded = encode_bytes(&buf);
+        assert_eq!(encoded, "VPRomVE");
+        assert_eq!(decode_to_bytes(&encoded).unwrap(), buf);
+        assert!(decode_to_bytes("VPRomV").is_err());
+    }
+}
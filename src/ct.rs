@@ -0,0 +1,87 @@
+//! Decoding secrets, such as keys, without branching on their contents
+
+use alloc::vec::Vec;
+
+use crate::{decode_strict, decoded_len, Error, Result, ALPHABET, DECODE_PAD_VALUE};
+
+/// decode_ct() is like `decode_strict()` but takes the same time for any valid input of a given
+/// length, so that an attacker who can time it learns nothing about the data being decoded.
+/// Each character is looked up by comparing it against the whole alphabet, and invalid
+/// characters and overflowing groups are gathered into a flag rather than returned as soon as
+/// they're seen.
+///
+/// What isn't hidden: the length of the input and whether it decoded at all. Invalid input is
+/// decoded again with `decode_strict()` to find the error, so errors take longer and report the
+/// offending offset. Whitespace isn't skipped since where it is would show in the timing. This is
+/// written to compile to branch-free code, but the compiler makes no promises about that.
+pub fn decode_ct<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    let indata = input.as_ref();
+    if indata.len() % 5 == 1 {
+        return Err(Error::InvalidLength(indata.len()));
+    }
+
+    let mut outdata = Vec::with_capacity(decoded_len(indata.len()));
+    let mut bad = 0u8;
+    for group in indata.chunks(5) {
+        let mut accumulator = 0u64;
+        for &c in group {
+            let (value, found) = lookup(c);
+            bad |= found ^ 1;
+            accumulator = accumulator * 85 + value as u64;
+        }
+        for _ in group.len()..5 {
+            accumulator = accumulator * 85 + DECODE_PAD_VALUE;
+        }
+
+        // The top bit is set only if the group is larger than a u32 can hold
+        bad |= ((u32::MAX as u64).wrapping_sub(accumulator) >> 63) as u8;
+
+        let len = if group.len() == 5 { 4 } else { group.len() - 1 };
+        outdata.extend_from_slice(&(accumulator as u32).to_be_bytes()[..len]);
+    }
+
+    if bad != 0 {
+        outdata.fill(0);
+        return Err(decode_strict(indata).expect_err("decode_strict() accepted invalid data"));
+    }
+    Ok(outdata)
+}
+
+// Looks up the value of a character without branching on it, returning the value and 1 if the
+// character is in the alphabet, or 0 and 0 if it isn't
+#[inline]
+fn lookup(c: u8) -> (u8, u8) {
+    let mut value = 0u8;
+    let mut found = 0u8;
+    for (i, &a) in ALPHABET.iter().enumerate() {
+        // 1 when c and a are equal, since only then does subtracting 1 wrap around
+        let equal = (((c ^ a) as u32).wrapping_sub(1) >> 31) as u8;
+        value |= (i as u8) & equal.wrapping_neg();
+        found |= equal;
+    }
+    (value, found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, encode};
+
+    #[test]
+    fn test_decode_ct() {
+        let data: Vec<u8> = (0..=255).collect();
+        for len in 0..data.len() {
+            assert_eq!(decode_ct(encode(&data[..len])).unwrap(), data[..len]);
+        }
+
+        for c in 0..=255 {
+            assert_eq!(lookup(c).1 == 1, ALPHABET.contains(&c));
+        }
+
+        for bad in ["VPR\"m", "|NsC1", "VPRom VE", "VPRomV"] {
+            assert_eq!(decode_ct(bad), decode_strict(bad));
+            assert!(decode_ct(bad).is_err());
+        }
+        assert!(decode("VPRom VE").is_ok());
+    }
+}
@@ -0,0 +1,123 @@
+//! The line format git uses for Base85 in binary patches, as written by `git diff --binary`.
+//!
+//! Git uses the RFC 1924 alphabet but splits the data into lines of up to 52 bytes. Each line
+//! starts with a character giving the number of bytes on it, `A` to `Z` for 1 to 26 and `a` to
+//! `z` for 27 to 52, followed by the bytes encoded as whole groups and a newline. A short last
+//! group is padded with zero bytes and written out in full, so every line holds a multiple of 5
+//! characters after the length. The data in a patch hunk is zlib-compressed, which is left to the
+//! caller.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{encode_group_with, Error, GroupDecoder, Result, RFC1924};
+
+// The most bytes git puts on one line
+const LINE_BYTES: usize = 52;
+
+/// encode() turns a slice of bytes into lines of git's binary patch format, each ending in a
+/// newline
+pub fn encode(indata: &[u8]) -> String {
+    let lines = indata.len().div_ceil(LINE_BYTES);
+    let mut outdata = String::with_capacity(lines * 67);
+    for line in indata.chunks(LINE_BYTES) {
+        let len = line.len() as u8;
+        outdata.push(if len <= 26 {
+            b'A' + len - 1
+        } else {
+            b'a' + len - 27
+        } as char);
+        for chunk in line.chunks(4) {
+            let mut group = [0u8; 4];
+            group[..chunk.len()].copy_from_slice(chunk);
+            for c in encode_group_with(&RFC1924, u32::from_be_bytes(group)) {
+                outdata.push(c as char);
+            }
+        }
+        outdata.push('\n');
+    }
+    outdata
+}
+
+/// decode() turns lines of git's binary patch format into a vector of bytes. Empty lines, like
+/// the one ending a hunk, are skipped. A bad length character is reported as
+/// `Error::InvalidCharacter`, and a line with the wrong number of characters for its length as
+/// `Error::InvalidLength` with the number it has.
+pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    let indata = input.as_ref();
+    let mut outdata = Vec::with_capacity(indata.len() / 5 * 4);
+    let mut offset = 0;
+    for line in indata.split(|&c| c == b'\n') {
+        let start = offset;
+        offset += line.len() + 1;
+        let Some((&len_char, chars)) = line.split_first() else {
+            continue;
+        };
+
+        let len = match len_char {
+            b'A'..=b'Z' => len_char - b'A' + 1,
+            b'a'..=b'z' => len_char - b'a' + 27,
+            _ => {
+                return Err(Error::InvalidCharacter {
+                    byte: len_char,
+                    offset: start,
+                })
+            }
+        } as usize;
+        if chars.len() != len.div_ceil(4) * 5 {
+            return Err(Error::InvalidLength(chars.len()));
+        }
+
+        let line_start = outdata.len();
+        let mut group = GroupDecoder::default();
+        for (i, &c) in chars.iter().enumerate() {
+            let offset = start + 1 + i;
+            let value = RFC1924.char85_to_byte(c, offset)?;
+            if let Some(bytes) = group.push(value, offset)? {
+                outdata.extend_from_slice(&bytes);
+            }
+        }
+        // Drop the padding of the last group
+        outdata.truncate(line_start + len);
+    }
+    Ok(outdata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git() {
+        // The hunk `git diff --binary` writes for a new 96 byte file, which holds the file
+        // compressed with zlib
+        let hunk = "zcmZQzX5-}H7ZR0_mQz$w*U~jGHnX&`cXIXc_6rONkBUu5PRq>6FDk95u4`;*@9Lc}\n\
+                    wdD_f5^A{~$v3lLcE!%hPJ#hHg$us9KUA=Mp-oq!)U%mbC`P<Jw{~6!_0EaCq)&Kwi\n";
+        let decoded = decode(hunk).unwrap();
+        assert_eq!(decoded.len(), 101);
+        assert_eq!(encode(&decoded), hunk);
+        assert_eq!(decode(alloc::format!("{hunk}\n")).unwrap(), decoded);
+
+        // A zlib stream starts with its header and ends with the Adler-32 checksum of the
+        // uncompressed data
+        let file: Vec<u8> = (0..=255).step_by(3).chain([0; 10]).collect();
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in &file {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        assert_eq!(decoded[..2], [0x78, 0x01]);
+        assert_eq!(decoded[97..], ((b << 16) | a).to_be_bytes());
+
+        assert_eq!(encode(b"a"), "AVE_OC\n");
+        assert_eq!(decode("AVE_OC").unwrap(), b"a");
+        assert_eq!(encode(b""), "");
+        assert!(matches!(decode("AVE\n"), Err(Error::InvalidLength(2))));
+        assert!(matches!(
+            decode("AVE_OC\n0VE_OC\n"),
+            Err(Error::InvalidCharacter {
+                byte: b'0',
+                offset: 7
+            })
+        ));
+    }
+}
@@ -4,265 +4,3431 @@
 //!
 //! Several variants of Base85 encoding exist. The most popular variant is often known as ascii85 and is best known for use in Adobe products. This is not that algorithm.
 //!
-//! The variant implemented in RFC 1924 was originally intended for encoding IPv6 addresses. It utilizes the same concepts as other versions, but uses a character set which is friendly toward embedding in source code without the need for escaping. During decoding ASCII whitespace (\n, \r, \t, space) is ignored. A base85-encoded string is 25% larger than the original binary data, which is more efficient than the more-common base64 algorithm (33%). This encoding pairs very well with JSON, yielding lower overhead and needing no character escapes.
+//! The variant implemented in RFC 1924 was originally intended for encoding IPv6 addresses. It utilizes the same concepts as other versions, but uses a character set which is friendly toward embedding in source code without the need for escaping. During decoding ASCII whitespace (\n, \r, \t, vertical tab, space) is ignored. A base85-encoded string is 25% larger than the original binary data, which is more efficient than the more-common base64 algorithm (33%). This encoding pairs very well with JSON, yielding lower overhead and needing no character escapes.
 //!
 //! ## Usage
 //!
-//! This was my first real Rust project but has matured since then and is stable. The API is simple: `encode()` turns a slice of bytes into a String and `decode()` turns a string reference into a Vector of bytes (u8). Both calls work completely within RAM, so processing huge files is probably not a good idea.
+//! This was my first real Rust project but has matured since then and is stable. The API is simple: `encode()` turns a slice of bytes into a String and `decode()` turns a string reference into a Vector of bytes (u8). Both calls work completely within RAM, so for huge files use `Encoder`, which wraps any `std::io::Write` and encodes data as it is written.
+//!
+//! ## Features
+//!
+//! The crate works without the standard library, needing only `alloc`. The `std` feature, which is
+//! on by default, adds the `Encoder` and `Decoder` streaming adapters, `encode_to_writer()` and
+//! `decode_armored()`. The `arrayvec` feature adds `decode_arrayvec()` for decoding into a
+//! fixed-size buffer on the stack. The `ascii85` feature adds the `ascii85` module for the Adobe
+//! variant. The `bytes` feature adds `encode_bytes()` and `decode_to_bytes()` for the bytes crate's
+//! buffers. The `serde` feature adds the `serde_bytes` module for storing byte fields as Base85
+//! strings. The `simd` feature speeds up encoding and decoding large inputs on x86 CPUs with SSE2
+//! or AVX2. The `smallvec` feature adds `decode_small()`, which decodes short data without
+//! allocating. The `rayon` feature adds `encode_parallel()` for encoding large inputs on several
+//! threads. The `test-vectors` feature adds the `test_vectors` module of known answers for checking
+//! other implementations. The `tokio` feature adds `AsyncEncoder` and `AsyncDecoder`, which work
+//! with tokio's `AsyncWrite` and `AsyncRead`. The `wasm` feature adds the `wasm` module, which
+//! exports `encode()` and `decode()` to JavaScript.
 //!
 //! ## Contributions
 //!
 //! Even though I've been coding for a while and have learned quite a bit about Rust, but I'm still a novice. Suggestions and contributions are always welcome and appreciated.
 
-pub type Result<T> = std::result::Result<T, Error>;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{borrow::Cow, boxed::Box, string::String, vec::Vec};
+use core::fmt;
+use core::net::Ipv6Addr;
+use core::str::FromStr;
 
-#[derive(thiserror::Error, Debug)]
+#[cfg(feature = "arrayvec")]
+mod array_vec;
+#[cfg(feature = "ascii85")]
+pub mod ascii85;
+#[cfg(feature = "tokio")]
+mod async_io;
+#[cfg(feature = "bytes")]
+mod bytes_buf;
+mod ct;
+pub mod git;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "serde")]
+pub mod serde_bytes;
+#[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+mod simd;
+#[cfg(feature = "smallvec")]
+mod small;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(any(test, feature = "test-vectors"))]
+pub mod test_vectors;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod z85;
+
+#[cfg(feature = "arrayvec")]
+pub use array_vec::decode_arrayvec;
+#[cfg(feature = "tokio")]
+pub use async_io::{AsyncDecoder, AsyncEncoder};
+#[cfg(feature = "bytes")]
+pub use bytes_buf::{decode_to_bytes, encode_bytes};
+pub use ct::decode_ct;
+#[cfg(feature = "rayon")]
+pub use parallel::encode_parallel;
+#[cfg(feature = "smallvec")]
+pub use small::{decode_small, decode_small_n};
+#[cfg(feature = "std")]
+pub use stream::{decode_armored, encode_reader, encode_to_writer, Decoder, Encoder};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Error {
-    #[error("Unexpected end of input")]
-    UnexpectedEof,
-    #[error("Unexpected character '{0}'")]
-    InvalidCharacter(u8),
+    /// The input ran out at `offset`, the length of the input, before the data was complete.
+    /// `in_group` tells whether it stopped partway through a group or between groups.
+    UnexpectedEof {
+        offset: usize,
+        in_group: bool,
+    },
+    InvalidCharacter {
+        byte: u8,
+        offset: usize,
+    },
+    InvalidLength(usize),
+    Overflow {
+        offset: usize,
+    },
+    InvalidAlphabet(u8),
+    BufferTooSmall,
+    ChecksumMismatch,
+    OutputTooLarge(usize),
+    InvalidUtf8 {
+        offset: usize,
+        source: core::str::Utf8Error,
+    },
+    /// A frame is cut short. For `decode_framed()` the offset is in the decoded data, and for
+    /// `decode_armored()` it's where the block with a missing marker starts in the input.
+    InvalidFrame {
+        offset: usize,
+    },
+    /// The group ending at `offset` decodes correctly but isn't how `encode()` writes its bytes.
+    /// The last character of a partial group carries some padding, so several characters there
+    /// decode the same, and `decode_canonical()` only accepts the one `encode()` uses.
+    NonCanonical {
+        offset: usize,
+    },
+    /// An error from `decode_debug()`, with the input around where it happened and a caret
+    /// under the offending character on the line below. Its source is the original error.
+    Context {
+        error: Box<Error>,
+        context: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnexpectedEof { offset, in_group } => write!(
+                f,
+                "Unexpected end of input at offset {offset}{}",
+                if *in_group { " inside a group" } else { "" }
+            ),
+            Error::InvalidCharacter { byte, offset } => {
+                write!(f, "Unexpected character '{byte}' at offset {offset}")
+            }
+            Error::InvalidLength(len) => write!(f, "Invalid input length {len}"),
+            Error::Overflow { offset } => write!(
+                f,
+                "Group starting at offset {offset} is too large to fit in 4 bytes"
+            ),
+            Error::InvalidAlphabet(c) => write!(
+                f,
+                "Character '{c}' is repeated or can't be used in an alphabet"
+            ),
+            Error::BufferTooSmall => f.write_str("Output buffer is too small"),
+            Error::ChecksumMismatch => f.write_str("Checksum doesn't match the data"),
+            Error::OutputTooLarge(limit) => write!(
+                f,
+                "Decoded data would be larger than the limit of {limit} bytes"
+            ),
+            Error::InvalidUtf8 { offset, .. } => {
+                write!(f, "Decoded data isn't valid UTF-8 at offset {offset}")
+            }
+            Error::InvalidFrame { offset } => {
+                write!(f, "Frame starting at offset {offset} is cut short")
+            }
+            Error::NonCanonical { offset } => {
+                write!(
+                    f,
+                    "Group ending at offset {offset} isn't encoded canonically"
+                )
+            }
+            Error::Context { error, context } => write!(f, "{error}\n{context}"),
+        }
+    }
 }
 
+// Written out rather than derived so that the source of `Error::Context` is the original error
+// itself, not the box holding it
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::InvalidUtf8 { source, .. } => Some(source),
+            Error::Context { error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+/// encoded_len() returns the number of characters `encode()` produces for `input_len` bytes.
+/// Every full group of 4 bytes becomes 5 characters and a trailing partial group of n bytes
+/// becomes n + 1 characters.
 #[inline]
-fn byte_to_char85(x85: u8) -> u8 {
-    static B85_TO_CHAR: &'static [u8] =
-        b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
-    B85_TO_CHAR[x85 as usize]
+pub const fn encoded_len(input_len: usize) -> usize {
+    let extra_bytes = input_len % 4;
+    (input_len / 4) * 5 + if extra_bytes != 0 { extra_bytes + 1 } else { 0 }
 }
 
+/// decoded_len() returns the number of bytes `decode()` produces for `encoded_len` characters
+/// of encoded data. Whitespace is skipped during decoding, so for input which contains any this
+/// is an upper bound rather than an exact count.
 #[inline]
-fn char85_to_byte(c: u8) -> Result<u8> {
-    match c {
-        b'0'..=b'9' => Ok(c - b'0'),
-        b'A'..=b'Z' => Ok(c - b'A' + 10),
-        b'a'..=b'z' => Ok(c - b'a' + 36),
-        b'!' => Ok(62),
-        b'#' => Ok(63),
-        b'$' => Ok(64),
-        b'%' => Ok(65),
-        b'&' => Ok(66),
-        b'(' => Ok(67),
-        b')' => Ok(68),
-        b'*' => Ok(69),
-        b'+' => Ok(70),
-        b'-' => Ok(71),
-        b';' => Ok(72),
-        b'<' => Ok(73),
-        b'=' => Ok(74),
-        b'>' => Ok(75),
-        b'?' => Ok(76),
-        b'@' => Ok(77),
-        b'^' => Ok(78),
-        b'_' => Ok(79),
-        b'`' => Ok(80),
-        b'{' => Ok(81),
-        b'|' => Ok(82),
-        b'}' => Ok(83),
-        b'~' => Ok(84),
-        v => Err(Error::InvalidCharacter(v)),
+pub const fn decoded_len(encoded_len: usize) -> usize {
+    let extra_chars = encoded_len % 5;
+    (encoded_len / 5) * 4 + if extra_chars != 0 { extra_chars - 1 } else { 0 }
+}
+
+/// OVERHEAD is how much larger encoded data is than the original once it's long enough for the
+/// partial group at the end not to matter, against 4/3 for base64
+pub const OVERHEAD: f64 = 1.25;
+
+/// overhead_ratio() returns how many times larger the output of `encode()` is than `input_len`
+/// bytes of input. It's `OVERHEAD` for whole groups and more when there's a partial group, up to
+/// 2 for a single byte. There's no expansion of empty input, so that's 1.
+pub fn overhead_ratio(input_len: usize) -> f64 {
+    if input_len == 0 {
+        return 1.0;
     }
+    encoded_len(input_len) as f64 / input_len as f64
+}
+
+/// ALPHABET is the characters of the RFC 1924 alphabet in order of value, for tools which need
+/// the exact character set
+pub const ALPHABET: [u8; 85] =
+    *b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// RFC1924 is the alphabet from RFC 1924, which `encode()` and `decode()` use
+pub const RFC1924: Alphabet = Alphabet::builtin(&ALPHABET);
+
+/// Z85 is the alphabet used by ZeroMQ's Z85 variant. Z85 also has rules about the length of its
+/// data, which the `z85` module enforces.
+pub const Z85: Alphabet = Alphabet::builtin(
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#",
+);
+
+/// ORDERED has the same characters as RFC1924 but in ascending ASCII order, so comparing encoded
+/// strings gives the same order as comparing the bytes they came from, which is useful for keys
+/// in sorted stores. This holds for data of the same length, or whose lengths are multiples of
+/// 4. Otherwise a partial group can sort differently from the full group it would have been.
+pub const ORDERED: Alphabet = Alphabet::builtin(
+    b"!#$%&()*+-0123456789;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ^_`abcdefghijklmnopqrstuvwxyz{|}~",
+);
+
+/// Alphabet is a set of 85 characters standing for the digits of encoded data. The several
+/// Base85 variants in use mostly differ only in their alphabet, so `encode_with()` and
+/// `decode_with()` can handle them given the right one.
+///
+/// No alphabet can survive a transport which changes the case of letters. There are only 94
+/// printable ASCII characters besides space, and leaving out either case of all 26 letters
+/// leaves 68, so every alphabet has to tell at least 17 letters apart by case alone. Encoded
+/// data has to be carried somewhere that leaves its case alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alphabet {
+    chars: [u8; 85],
+    // Maps each byte to its value, with -1 marking bytes outside of the alphabet
+    values: [i8; 256],
 }
 
-/// encode() turns a slice of bytes into a string of encoded data
-pub fn encode(indata: &[u8]) -> String {
-    if indata.len() == 0 {
-        return String::from("");
+impl Alphabet {
+    /// new() creates an Alphabet from 85 characters, the first standing for 0 and the last for
+    /// 84. The characters must be distinct ASCII and can't be whitespace, which is skipped while
+    /// decoding. Otherwise `Error::InvalidAlphabet` is returned with the first offending
+    /// character.
+    pub const fn new(chars: &[u8; 85]) -> Result<Alphabet> {
+        match Alphabet::value_table(chars) {
+            Ok(values) => Ok(Alphabet {
+                chars: *chars,
+                values,
+            }),
+            Err(c) => Err(Error::InvalidAlphabet(c)),
+        }
+    }
+
+    // Creates one of the alphabets built into the crate. Error can't be dropped in a const, so
+    // this works on the bare value table rather than going through new().
+    const fn builtin(chars: &[u8; 85]) -> Alphabet {
+        match Alphabet::value_table(chars) {
+            Ok(values) => Alphabet {
+                chars: *chars,
+                values,
+            },
+            Err(_) => panic!("a built-in alphabet is invalid"),
+        }
+    }
+
+    // Builds the table of character values, or returns the first character which can't be used
+    const fn value_table(chars: &[u8; 85]) -> core::result::Result<[i8; 256], u8> {
+        let mut values = [-1; 256];
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if !c.is_ascii() || is_whitespace(c) || values[c as usize] != -1 {
+                return Err(c);
+            }
+            values[c as usize] = i as i8;
+            i += 1;
+        }
+        Ok(values)
+    }
+
+    /// chars() returns the characters of the alphabet in order of value
+    pub const fn chars(&self) -> &[u8; 85] {
+        &self.chars
+    }
+
+    #[inline]
+    fn byte_to_char85(&self, x85: u8) -> u8 {
+        self.chars[x85 as usize]
     }
 
-    let mut outdata: Vec<u8> = Vec::new();
+    // Looks up the value of a character, using its offset in the input to report it if it's
+    // invalid
+    #[inline]
+    fn char85_to_byte(&self, c: u8, offset: usize) -> Result<u8> {
+        match self.values[c as usize] {
+            -1 => Err(Error::InvalidCharacter { byte: c, offset }),
+            v => Ok(v as u8),
+        }
+    }
+}
 
-    let length = indata.len();
-    let chunk_count = (length / 4) as u32;
-    let mut data_index: usize = 0;
+// The ASCII whitespace characters skipped during decoding
+#[inline]
+const fn is_whitespace(c: u8) -> bool {
+    matches!(c, b' ' | b'\n' | b'\r' | b'\t' | 0x0b)
+}
 
-    for _i in 0..chunk_count {
-        let decnum: u32 = (indata[data_index] as u32).overflowing_shl(24).0
-            | (indata[data_index + 1] as u32).overflowing_shl(16).0
-            | (indata[data_index + 2] as u32).overflowing_shl(8).0
-            | indata[data_index + 3] as u32;
+/// is_base85_char() returns whether a byte is one of the characters of the RFC 1924 alphabet
+#[inline]
+pub const fn is_base85_char(c: u8) -> bool {
+    RFC1924.values[c as usize] != -1
+}
 
-        outdata.push(byte_to_char85((decnum as usize / 52200625) as u8));
-        let mut remainder = decnum as usize % 52200625;
-        outdata.push(byte_to_char85((remainder / 614125) as u8));
+/// is_base85_whitespace() returns whether a byte is whitespace which `decode()` skips: space,
+/// \n, \r, \t or vertical tab (0x0b)
+#[inline]
+pub const fn is_base85_whitespace(c: u8) -> bool {
+    is_whitespace(c)
+}
 
-        remainder %= 614125;
-        outdata.push(byte_to_char85((remainder / 7225) as u8));
+/// encode() turns a slice of bytes into a string of encoded data. Anything which can be viewed as
+/// bytes can be passed, such as a `&str`, `String`, or `Vec<u8>`.
+pub fn encode<T: AsRef<[u8]>>(indata: T) -> String {
+    let indata = indata.as_ref();
+    let mut outdata = String::with_capacity(encoded_len(indata.len()));
+    encode_append(&RFC1924, indata, &mut outdata);
+    outdata
+}
 
-        remainder %= 7225;
-        outdata.push(byte_to_char85((remainder / 85) as u8));
+/// encode_to_bytes() is like `encode()` but returns the encoded ASCII characters as bytes, for
+/// handing straight to something like `write_all()`. The characters are never checked for
+/// UTF-8 along the way, so this costs nothing over `encode()`.
+pub fn encode_to_bytes<T: AsRef<[u8]>>(indata: T) -> Vec<u8> {
+    encode(indata).into_bytes()
+}
 
-        outdata.push(byte_to_char85((remainder % 85) as u8));
+// The number of bytes or characters handled between calls to a progress callback. This has to
+// be a multiple of 4 so that only the last chunk can have a partial group when encoding.
+const PROGRESS_CHUNK_LEN: usize = 0x10000;
 
-        data_index += 4;
+/// encode_with_progress() is like `encode()` but calls `progress` with the number of bytes
+/// encoded so far and the total after every 64 KiB, and once at the end
+pub fn encode_with_progress<T: AsRef<[u8]>, F: FnMut(usize, usize)>(
+    indata: T,
+    mut progress: F,
+) -> String {
+    let indata = indata.as_ref();
+    let mut outdata = String::with_capacity(encoded_len(indata.len()));
+    let mut done = 0;
+    for chunk in indata.chunks(PROGRESS_CHUNK_LEN) {
+        encode_append(&RFC1924, chunk, &mut outdata);
+        done += chunk.len();
+        progress(done, indata.len());
     }
+    if indata.is_empty() {
+        progress(0, 0);
+    }
+    outdata
+}
+
+/// encode_str() encodes the UTF-8 bytes of a string
+pub fn encode_str(s: &str) -> String {
+    encode(s)
+}
 
-    let extra_bytes = length % 4;
-    if extra_bytes != 0 {
-        let mut last_chunk = 0_u32;
+/// encode_with() turns a slice of bytes into a string of encoded data using the characters of
+/// `alphabet`
+pub fn encode_with<T: AsRef<[u8]>>(alphabet: &Alphabet, indata: T) -> String {
+    let mut outdata = String::new();
+    encode_append(alphabet, indata.as_ref(), &mut outdata);
+    outdata
+}
+
+/// Endianness is the order in which the 4 bytes of a group are packed into the number that's
+/// encoded. RFC 1924 is big-endian, but some implementations use little-endian. Either way, a
+/// partial group at the end is packed big-endian, since only its most significant digits are
+/// kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
+
+/// EncodeOptions controls how data is encoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodeOptions<'a> {
+    /// The alphabet to encode with, `RFC1924` by default
+    pub alphabet: &'a Alphabet,
+    /// How groups of bytes are packed, big-endian by default
+    pub endianness: Endianness,
+}
 
-        for i in length - extra_bytes..length {
-            last_chunk = last_chunk.overflowing_shl(8).0;
-            last_chunk |= indata[i] as u32;
+impl Default for EncodeOptions<'_> {
+    fn default() -> Self {
+        EncodeOptions {
+            alphabet: &RFC1924,
+            endianness: Endianness::Big,
         }
+    }
+}
 
-        // Pad extra bytes with zeroes
-        {
-            let mut i = 4 - extra_bytes;
-            while i > 0 {
-                last_chunk = last_chunk.overflowing_shl(8).0;
-                i -= 1;
+/// encode_with_options() turns a slice of bytes into a string of encoded data according to
+/// `options`
+pub fn encode_with_options<T: AsRef<[u8]>>(indata: T, options: &EncodeOptions) -> String {
+    let indata = indata.as_ref();
+    let mut outdata = String::with_capacity(encoded_len(indata.len()));
+    match options.endianness {
+        Endianness::Big => encode_append(options.alphabet, indata, &mut outdata),
+        Endianness::Little => {
+            let mut chunks = indata.chunks_exact(4);
+            for chunk in chunks.by_ref() {
+                let decnum = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                for c in encode_group_with(options.alphabet, decnum) {
+                    outdata.push(c as char);
+                }
             }
+            encode_append(options.alphabet, chunks.remainder(), &mut outdata);
         }
+    }
+    outdata
+}
+
+/// encode_into() turns a slice of bytes into encoded data, replacing the contents of `out`.
+/// The string's existing allocation is reused, so encoding repeatedly into the same buffer
+/// only allocates when the output grows past what it has held before.
+pub fn encode_into<T: AsRef<[u8]>>(indata: T, out: &mut String) {
+    out.clear();
+    encode_append(&RFC1924, indata.as_ref(), out);
+}
+
+fn encode_append(alphabet: &Alphabet, indata: &[u8], out: &mut String) {
+    if indata.is_empty() {
+        return;
+    }
+
+    out.reserve(encoded_len(indata.len()));
+
+    #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+    let indata = {
+        // SAFETY: the vector code only writes characters from the alphabet, which are ASCII,
+        // and whatever it doesn't write is truncated away again
+        let outdata = unsafe { out.as_mut_vec() };
+        let start = outdata.len();
+        outdata.resize(start + indata.len() / 4 * 5, 0);
+        let used = simd::encode_groups(alphabet, indata, &mut outdata[start..]);
+        outdata.truncate(start + used / 4 * 5);
+        &indata[used..]
+    };
+
+    encode_scalar(alphabet, indata, out);
+}
+
+fn encode_scalar(alphabet: &Alphabet, indata: &[u8], out: &mut String) {
+    // SAFETY: every character comes from the alphabet, which Alphabet::new() only allows to hold
+    // ASCII, so the bytes can be appended without checking them for valid UTF-8. The debug
+    // assertion at the end catches any bug that lets other bytes through.
+    let outdata = unsafe { out.as_mut_vec() };
+    let start = outdata.len();
+
+    let mut chunks = indata.chunks_exact(4);
+    for chunk in chunks.by_ref() {
+        let decnum = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        outdata.extend_from_slice(&encode_group_with(alphabet, decnum));
+    }
+
+    let extra = chunks.remainder();
+    if !extra.is_empty() {
+        let tail = encode_tail(alphabet, extra);
+        outdata.extend_from_slice(&tail[..extra.len() + 1]);
+    }
+
+    debug_assert!(outdata[start..].is_ascii());
+}
+
+/// Base85Encoder encodes data which arrives in pieces, such as from an async stream, without
+/// needing `std::io`. Bytes are encoded in groups of 4, so up to 3 bytes are carried over from one
+/// `update()` to the next and the encoded output only depends on the data, not on how it was
+/// split up.
+#[derive(Clone, Debug, Default)]
+pub struct Base85Encoder {
+    pending: [u8; 4],
+    pending_len: usize,
+}
+
+impl Base85Encoder {
+    /// new() creates a Base85Encoder with no data
+    pub fn new() -> Base85Encoder {
+        Base85Encoder::default()
+    }
+
+    /// update() encodes the next piece of data, returning the characters for every group it
+    /// completes
+    pub fn update(&mut self, chunk: &[u8]) -> String {
+        let mut outdata = String::with_capacity((self.pending_len + chunk.len()) / 4 * 5);
+        self.update_into(chunk, &mut outdata);
+        outdata
+    }
 
-        outdata.push(byte_to_char85((last_chunk as usize / 52200625) as u8));
-        let mut remainder = last_chunk as usize % 52200625;
-        outdata.push(byte_to_char85((remainder / 614125) as u8));
+    // Does the work of update(), appending to `outdata`
+    fn update_into(&mut self, chunk: &[u8], outdata: &mut String) {
+        let mut indata = chunk;
 
-        if extra_bytes > 1 {
-            remainder %= 614125;
-            outdata.push(byte_to_char85((remainder / 7225) as u8));
+        // Top off a group left over from the last call first
+        if self.pending_len > 0 {
+            let needed = (4 - self.pending_len).min(indata.len());
+            self.pending[self.pending_len..self.pending_len + needed]
+                .copy_from_slice(&indata[..needed]);
+            self.pending_len += needed;
+            indata = &indata[needed..];
 
-            if extra_bytes > 2 {
-                remainder %= 7225;
-                outdata.push(byte_to_char85((remainder / 85) as u8));
+            if self.pending_len < 4 {
+                return;
             }
+            encode_append(&RFC1924, &self.pending, outdata);
+            self.pending_len = 0;
         }
+
+        let whole = indata.len() - indata.len() % 4;
+        encode_append(&RFC1924, &indata[..whole], outdata);
+
+        let extra = &indata[whole..];
+        self.pending[..extra.len()].copy_from_slice(extra);
+        self.pending_len = extra.len();
+    }
+
+    /// finalize() encodes the bytes carried over from the last `update()`, if any
+    pub fn finalize(mut self) -> String {
+        let mut outdata = String::new();
+        self.finish_into(&mut outdata);
+        outdata
     }
 
-    String::from_utf8(outdata).unwrap()
+    // Does the work of finalize(), appending to `outdata`, for the adapters which hold an encoder
+    // rather than owning one to give away
+    fn finish_into(&mut self, outdata: &mut String) {
+        encode_append(&RFC1924, &self.pending[..self.pending_len], outdata);
+        self.pending_len = 0;
+    }
 }
 
-/// decode() turns a string of encoded data into a slice of bytes
-pub fn decode(instr: &str) -> Result<Vec<u8>> {
-    let length = instr.len() as u32;
-    let mut outdata = Vec::<u8>::new();
-    let mut accumulator;
-    let mut in_index = instr.bytes();
-
-    for _chunk in 0..length / 5 {
-        accumulator = 0;
-
-        // This construct is a bit strange because Rust doesn't let us modify the index variable
-        // in a for loop
-        {
-            let mut i = 0;
-            while i < 5 {
-                let b = match in_index.next() {
-                    Some(n) => n,
-                    _ => break,
-                };
-                match b {
-                    32 | 10 | 11 | 13 => {
-                        // Ignore whitespace
-                        continue;
-                    }
-                    _ => {}
-                }
+/// encode_many() encodes each input on its own, as if by mapping `encode()` over them. Every
+/// input that isn't a multiple of 4 bytes long ends in its own partial group, so joining the
+/// results doesn't give the same string as `encode_concat()`.
+pub fn encode_many(inputs: &[&[u8]]) -> Vec<String> {
+    inputs.iter().map(encode).collect()
+}
 
-                accumulator = (accumulator * 85) + char85_to_byte(b)? as u32;
-                i += 1;
-            }
-        }
-        outdata.push((accumulator >> 24) as u8);
-        outdata.push(((accumulator >> 16) & 255) as u8);
-        outdata.push(((accumulator >> 8) & 255) as u8);
-        outdata.push((accumulator & 255) as u8);
-    }
-
-    let remainder = length % 5;
-    if remainder > 0 {
-        accumulator = 0;
-        {
-            let mut i = 0;
-            while i < 5 {
-                let value: u8;
-
-                if i < remainder {
-                    let b = match in_index.next() {
-                        Some(n) => n,
-                        _ => break,
-                    };
-                    match b {
-                        32 | 10 | 11 | 13 => {
-                            // Ignore whitespace
-                            continue;
-                        }
-                        _ => {}
-                    }
+/// encode_concat() encodes the inputs as one stream of bytes, exactly as `encode()` would encode
+/// them joined together, but without copying them into one buffer first. Groups can span the
+/// boundaries between inputs, so the output can't be split back up into the separate inputs.
+pub fn encode_concat(inputs: &[&[u8]]) -> String {
+    let total = inputs.iter().map(|input| input.len()).sum();
+    let mut outdata = String::with_capacity(encoded_len(total));
+    let mut encoder = Base85Encoder::new();
+    for input in inputs {
+        encoder.update_into(input, &mut outdata);
+    }
+    encoder.finish_into(&mut outdata);
+    outdata
+}
 
-                    value = char85_to_byte(b)?;
-                } else {
-                    value = 126;
-                }
-                accumulator = (accumulator * 85) + value as u32;
-                i += 1;
-            }
+/// encode_framed() encodes a list of messages so that `decode_framed()` can split them apart
+/// again, which separately encoded messages joined together can't do. Each message is prefixed
+/// with its length as 4 big-endian bytes, and the whole lot is encoded as one stream with
+/// `encode()`. This panics if a message is 4 GiB or longer.
+pub fn encode_framed(messages: &[&[u8]]) -> String {
+    let total = messages.iter().map(|m| m.len() + 4).sum();
+    let mut framed = Vec::with_capacity(total);
+    for message in messages {
+        let len = u32::try_from(message.len()).expect("message is too long to frame");
+        framed.extend_from_slice(&len.to_be_bytes());
+        framed.extend_from_slice(message);
+    }
+    encode(framed)
+}
+
+/// decode_framed() decodes data from `encode_framed()` back into the list of messages. If a
+/// length prefix or the message after it runs past the end of the data, `Error::InvalidFrame`
+/// is returned with the offset of the frame in the decoded data.
+pub fn decode_framed<T: AsRef<[u8]>>(input: T) -> Result<Vec<Vec<u8>>> {
+    let framed = decode(input)?;
+    let mut messages = Vec::new();
+    let mut rest = &framed[..];
+    while !rest.is_empty() {
+        let offset = framed.len() - rest.len();
+        let message = rest
+            .get(..4)
+            .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+            .and_then(|len| rest[4..].get(..len))
+            .ok_or(Error::InvalidFrame { offset })?;
+        messages.push(message.to_vec());
+        rest = &rest[4 + message.len()..];
+    }
+    Ok(messages)
+}
+
+/// encode_blocks() is like `encode()` but only encodes whole groups, for fixed-size records. The
+/// input must be a multiple of 4 bytes long, otherwise `Error::InvalidLength` is returned.
+pub fn encode_blocks<T: AsRef<[u8]>>(indata: T) -> Result<String> {
+    encode_blocks_with(&RFC1924, indata.as_ref())
+}
+
+// Encodes whole groups only, as both encode_blocks() and Z85 require
+fn encode_blocks_with(alphabet: &Alphabet, indata: &[u8]) -> Result<String> {
+    if indata.len() % 4 != 0 {
+        return Err(Error::InvalidLength(indata.len()));
+    }
+
+    let mut outdata = String::with_capacity(encoded_len(indata.len()));
+    encode_append(alphabet, indata, &mut outdata);
+    Ok(outdata)
+}
+
+/// encode_exact() encodes data as whole groups, padding the last one with zero bytes, and
+/// appends one more character which gives the number of bytes in that last group: `0` when the
+/// input is a multiple of 4 bytes long, otherwise `1` to `3`. `decode_exact()` uses it to cut the
+/// padding off again, so the length of the data never has to be inferred from a partial group.
+pub fn encode_exact<T: AsRef<[u8]>>(indata: T) -> String {
+    let indata = indata.as_ref();
+    let mut outdata = String::with_capacity(indata.len().div_ceil(4) * 5 + 1);
+    let (groups, extra) = indata.split_at(indata.len() - indata.len() % 4);
+    encode_append(&RFC1924, groups, &mut outdata);
+    if !extra.is_empty() {
+        let mut last_chunk = [ENCODE_PAD_BYTE; 4];
+        last_chunk[..extra.len()].copy_from_slice(extra);
+        for c in encode_group_with(&RFC1924, u32::from_be_bytes(last_chunk)) {
+            outdata.push(c as char);
+        }
+    }
+    outdata.push(ALPHABET[extra.len()] as char);
+    outdata
+}
+
+/// decode_exact() is the counterpart to `encode_exact()`. Whitespace isn't allowed. A final
+/// character other than `0` to `3`, or one which doesn't fit the data before it, is reported as
+/// `Error::InvalidCharacter`, and data before it which isn't a multiple of 5 characters long as
+/// `Error::InvalidLength`.
+pub fn decode_exact<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    let indata = input.as_ref();
+    let Some((&last, groups)) = indata.split_last() else {
+        return Err(Error::InvalidLength(0));
+    };
+
+    let offset = groups.len();
+    let extra = match last {
+        b'0'..=b'3' if groups.len() >= 5 || last == b'0' => (last - b'0') as usize,
+        _ => return Err(Error::InvalidCharacter { byte: last, offset }),
+    };
+    let mut outdata = decode_blocks_with(&RFC1924, groups)?;
+    if extra > 0 {
+        outdata.truncate(outdata.len() - 4 + extra);
+    }
+    Ok(outdata)
+}
+
+/// encode_slice() encodes a slice of bytes into the start of `out` without allocating and returns
+/// the number of characters written. If `out` is shorter than `encoded_len(indata.len())`,
+/// nothing is written and `Error::BufferTooSmall` is returned.
+pub fn encode_slice<T: AsRef<[u8]>>(indata: T, out: &mut [u8]) -> Result<usize> {
+    let indata = indata.as_ref();
+    let needed = encoded_len(indata.len());
+    if out.len() < needed {
+        return Err(Error::BufferTooSmall);
+    }
+
+    // The vector code handles what it can first, as in encode()
+    #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+    let (indata, written) = {
+        let used = simd::encode_groups(&RFC1924, indata, out);
+        (&indata[used..], used / 4 * 5)
+    };
+    #[cfg(not(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64"))))]
+    let written = 0;
+
+    let mut chunks = indata.chunks_exact(4);
+    for (chunk, outchunk) in chunks.by_ref().zip(out[written..].chunks_exact_mut(5)) {
+        let decnum = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        outchunk.copy_from_slice(&encode_group_with(&RFC1924, decnum));
+    }
+
+    let extra = chunks.remainder();
+    if !extra.is_empty() {
+        let tail = encode_tail(&RFC1924, extra);
+        out[needed - extra.len() - 1..needed].copy_from_slice(&tail[..extra.len() + 1]);
+    }
+
+    Ok(needed)
+}
+
+/// Base85Display encodes its bytes as they are formatted, so they can be written with `format!()`
+/// or `write!()` without building an intermediate String. Width and other formatting flags are
+/// ignored.
+#[derive(Clone, Copy, Debug)]
+pub struct Base85Display<'a>(pub &'a [u8]);
+
+impl fmt::Display for Base85Display<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        encode_fmt(self.0, f)
+    }
+}
+
+// The number of bytes encode_fmt() encodes at a time. This has to be a multiple of 4 so that
+// only the last chunk can have a partial group.
+const FMT_CHUNK_LEN: usize = 128;
+
+/// encode_fmt() encodes a slice of bytes straight into any `fmt::Write`, such as an existing
+/// `String`, a little at a time and without allocating. This is the counterpart to
+/// `encode_to_writer()` for when `std::io` isn't available.
+pub fn encode_fmt<W: fmt::Write>(indata: &[u8], w: &mut W) -> fmt::Result {
+    let mut outdata = [0u8; encoded_len(FMT_CHUNK_LEN)];
+    for chunk in indata.chunks(FMT_CHUNK_LEN) {
+        let len = encode_slice(chunk, &mut outdata).map_err(|_| fmt::Error)?;
+        w.write_str(core::str::from_utf8(&outdata[..len]).map_err(|_| fmt::Error)?)?;
+    }
+    Ok(())
+}
+
+/// Base85String holds encoded data, keeping it apart from arbitrary text in function signatures.
+/// Encoding with `From` always succeeds. Text from elsewhere can be wrapped with
+/// `from_encoded()`, which doesn't check it, so decoding it may fail.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Base85String(String);
+
+impl Base85String {
+    /// from_encoded() wraps text which should already be encoded data. It is only checked when
+    /// it's decoded.
+    pub fn from_encoded(encoded: String) -> Base85String {
+        Base85String(encoded)
+    }
+
+    /// decode() decodes the data back into bytes
+    pub fn decode(&self) -> Result<Vec<u8>> {
+        decode(&self.0)
+    }
+
+    /// as_str() returns the encoded data
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// into_string() returns the encoded data, giving up the wrapper
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl From<&[u8]> for Base85String {
+    fn from(indata: &[u8]) -> Base85String {
+        Base85String(encode(indata))
+    }
+}
+
+impl TryFrom<&Base85String> for Vec<u8> {
+    type Error = Error;
+
+    fn try_from(encoded: &Base85String) -> Result<Vec<u8>> {
+        encoded.decode()
+    }
+}
+
+impl TryFrom<Base85String> for Vec<u8> {
+    type Error = Error;
+
+    fn try_from(encoded: Base85String) -> Result<Vec<u8>> {
+        encoded.decode()
+    }
+}
+
+impl AsRef<str> for Base85String {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Base85String {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// DecodedBytes holds bytes decoded from text by `str::parse()`, for argument and config parsers
+/// which build their values with `FromStr`. An invalid string fails to parse with the crate's
+/// `Error`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DecodedBytes(pub Vec<u8>);
+
+impl FromStr for DecodedBytes {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<DecodedBytes> {
+        decode(s).map(DecodedBytes)
+    }
+}
+
+impl From<DecodedBytes> for Vec<u8> {
+    fn from(decoded: DecodedBytes) -> Vec<u8> {
+        decoded.0
+    }
+}
+
+impl AsRef<[u8]> for DecodedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// encode_array() encodes fixed-size data such as a UUID, taken by value. It does the same work
+/// as `encode()`, and `encode_array_const()` encodes into an array instead of a String.
+#[inline]
+pub fn encode_array<const N: usize>(data: [u8; N]) -> String {
+    encode(data)
+}
+
+/// encode_array_const() encodes fixed-size data into an array without allocating, and can be
+/// used to encode constants at compile time. `M` has to be `encoded_len(N)`, which is checked
+/// when the program is compiled, so a 16 byte UUID encodes into a `[u8; 20]`.
+pub const fn encode_array_const<const N: usize, const M: usize>(data: &[u8; N]) -> [u8; M] {
+    const { assert!(M == encoded_len(N), "M must be encoded_len(N)") };
+
+    let mut outdata = [0u8; M];
+    let mut i = 0;
+    while i < N {
+        let len = if N - i < 4 { N - i } else { 4 };
+
+        let mut group = [ENCODE_PAD_BYTE; 4];
+        let mut j = 0;
+        while j < len {
+            group[j] = data[i + j];
+            j += 1;
+        }
+
+        // A partial group only needs its first len + 1 characters, like encode_tail()
+        let digits = group_digits(u32::from_be_bytes(group));
+        let start = i / 4 * 5;
+        let mut j = 0;
+        while j <= len {
+            outdata[start + j] = RFC1924.chars[digits[j] as usize];
+            j += 1;
+        }
+
+        i += len;
+    }
+    outdata
+}
+
+/// decode_array_const() is the counterpart to `encode_array_const()`, for decoding constants at
+/// compile time. `M` has to be `decoded_len(N)`. There's no way to report an error from a
+/// constant, so data which `decode_strict()` would reject makes this panic, which fails the
+/// build when it's used for a constant.
+pub const fn decode_array_const<const N: usize, const M: usize>(encoded: &[u8; N]) -> [u8; M] {
+    const {
+        assert!(M == decoded_len(N), "M must be decoded_len(N)");
+        assert!(N % 5 != 1, "encoded data can't end with a lone character");
+    };
+
+    let mut outdata = [0u8; M];
+    let mut i = 0;
+    while i < N {
+        let len = if N - i < 5 { N - i } else { 5 };
+
+        let mut accumulator = 0u64;
+        let mut j = 0;
+        while j < 5 {
+            let value = if j < len {
+                let value = RFC1924.values[encoded[i + j] as usize];
+                assert!(value >= 0, "invalid character in encoded data");
+                value as u64
+            } else {
+                DECODE_PAD_VALUE
+            };
+            accumulator = accumulator * 85 + value;
+            j += 1;
         }
+        assert!(group_fits_u32(accumulator), "group too large for 4 bytes");
 
-        match remainder {
-            4 => {
-                outdata.push((accumulator >> 24) as u8);
-                outdata.push(((accumulator >> 16) & 255) as u8);
-                outdata.push(((accumulator >> 8) & 255) as u8);
+        // A partial group of len characters holds len - 1 bytes
+        let bytes = (accumulator as u32).to_be_bytes();
+        let start = i / 5 * 4;
+        let mut j = 0;
+        while j < len - 1 {
+            outdata[start + j] = bytes[j];
+            j += 1;
+        }
+
+        i += len;
+    }
+    outdata
+}
+
+/// encode_wrapped() is like `encode()` but breaks the output into lines of `line_len`
+/// characters, separated by `\n` with none after the last line. `decode()` skips the line breaks,
+/// so the result decodes the same as unwrapped output. A `line_len` of 0 disables wrapping.
+pub fn encode_wrapped<T: AsRef<[u8]>>(indata: T, line_len: usize) -> String {
+    let encoded = encode(indata);
+    if line_len == 0 || encoded.len() <= line_len {
+        return encoded;
+    }
+
+    let mut outdata = String::with_capacity(encoded.len() + (encoded.len() - 1) / line_len);
+    for (i, c) in encoded.chars().enumerate() {
+        if i > 0 && i % line_len == 0 {
+            outdata.push('\n');
+        }
+        outdata.push(c);
+    }
+    outdata
+}
+
+/// encode_line() is like `encode()` but ends the output with `\n`, so written to a file on its own
+/// it makes a proper POSIX text file. `decode()` skips the newline.
+pub fn encode_line<T: AsRef<[u8]>>(indata: T) -> String {
+    encode_wrapped_line(indata, 0)
+}
+
+/// encode_wrapped_line() is like `encode_wrapped()` but also ends the last line with `\n`
+pub fn encode_wrapped_line<T: AsRef<[u8]>>(indata: T, line_len: usize) -> String {
+    let mut outdata = encode_wrapped(indata, line_len);
+    outdata.push('\n');
+    outdata
+}
+
+/// encode_iter() lazily encodes bytes from an iterator, yielding the encoded characters one at a
+/// time instead of collecting them into a String
+pub fn encode_iter<I: IntoIterator<Item = u8>>(bytes: I) -> impl Iterator<Item = char> {
+    EncodeIter {
+        inner: bytes.into_iter(),
+        outbuf: [0; 5],
+        out_pos: 0,
+        out_len: 0,
+        finished: false,
+    }
+}
+
+struct EncodeIter<I> {
+    inner: I,
+    outbuf: [u8; 5],
+    out_pos: usize,
+    out_len: usize,
+    finished: bool,
+}
+
+impl<I: Iterator<Item = u8>> Iterator for EncodeIter<I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.out_pos == self.out_len {
+            if self.finished {
+                return None;
             }
-            3 => {
-                outdata.push((accumulator >> 24) as u8);
-                outdata.push(((accumulator >> 16) & 255) as u8);
+
+            let mut chunk = [0u8; 4];
+            let mut chunk_len = 0;
+            while chunk_len < 4 {
+                match self.inner.next() {
+                    Some(b) => {
+                        chunk[chunk_len] = b;
+                        chunk_len += 1;
+                    }
+                    None => {
+                        self.finished = true;
+                        break;
+                    }
+                }
             }
-            2 => {
-                outdata.push((accumulator >> 24) as u8);
+
+            match chunk_len {
+                0 => return None,
+                4 => {
+                    self.outbuf = encode_group_with(&RFC1924, u32::from_be_bytes(chunk));
+                    self.out_len = 5;
+                }
+                _ => {
+                    self.outbuf = encode_tail(&RFC1924, &chunk[..chunk_len]);
+                    self.out_len = chunk_len + 1;
+                }
             }
-            _ => panic!(),
+            self.out_pos = 0;
         }
-    }
 
-    Ok(outdata)
+        let c = self.outbuf[self.out_pos];
+        self.out_pos += 1;
+        Some(c as char)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::*;
+// The place value of the first digit of a group
+const POW85_4: u32 = 85 * 85 * 85 * 85;
 
-    #[test]
-    fn test_encode_decode() {
-        // The list of tests consists of the unencoded data on the left and the encoded data on
-        // the right. By using strings for the arbitrary binary data, we make the test much less
-        // complicated to write.
-        let testlist = [
-            ("a", "VE"),
-            ("aa", "VPO"),
-            ("aaa", "VPRn"),
-            ("aaaa", "VPRom"),
-            ("aaaaa", "VPRomVE"),
-            ("aaaaaa", "VPRomVPO"),
-            ("aaaaaaa", "VPRomVPRn"),
-            ("aaaaaaaa", "VPRomVPRom"),
-        ];
+// 2^64 / 85^4, rounded up, for splitting a group into digits without dividing
+const INV_POW85_4: u64 = ((1u128 << 64).div_ceil(POW85_4 as u128)) as u64;
 
-        for test in testlist.iter() {
-            let s = encode(test.0.as_bytes());
-            assert_eq!(
-                s, test.1,
-                "encoder test failed: wanted: {}, got: {}",
-                test.0, s
-            );
+/// MAX_GROUP_VALUE is the largest value 5 characters can hold, 85^5 - 1. That's more than a u32
+/// can hold, so not every group of characters decodes to 4 bytes.
+pub const MAX_GROUP_VALUE: u64 = 85u64.pow(5) - 1;
 
-            let b = decode(test.1)
-                .unwrap_or_else(|e| panic!("decoder test error on input {}: {}", test.1, e));
+/// group_fits_u32() returns whether the value of a group of 5 characters fits in 4 bytes. The
+/// encoder never produces a group that doesn't, so decoding rejects them with
+/// `Error::Overflow`.
+#[inline]
+pub const fn group_fits_u32(value: u64) -> bool {
+    value <= u32::MAX as u64
+}
 
-            let s = String::from_utf8(b).unwrap_or_else(|e| {
-                panic!(
-                    "decoder test '{}' failed to convert to string: {:#?}",
-                    test.1, e
-                )
+/// encode_group() encodes one full group of 4 bytes into its 5 characters, for building other
+/// framing or streaming on top of the group math
+#[inline]
+pub fn encode_group(chunk: &[u8; 4]) -> [u8; 5] {
+    encode_group_with(&RFC1924, u32::from_be_bytes(*chunk))
+}
+
+/// decode_group() decodes one full group of 5 characters back into its 4 bytes. Offsets in
+/// errors count from the start of the group, and a group too large for 4 bytes is reported as
+/// `Error::Overflow`.
+#[inline]
+pub fn decode_group(chunk: &[u8; 5]) -> Result<[u8; 4]> {
+    let mut accumulator = 0u64;
+    for (offset, &c) in chunk.iter().enumerate() {
+        accumulator = accumulator * 85 + RFC1924.char85_to_byte(c, offset)? as u64;
+    }
+    Ok(check_group(accumulator, 0)?.to_be_bytes())
+}
+
+// Turns a group of 4 bytes, packed big-endian into a u32, into 5 characters
+#[inline]
+fn encode_group_with(alphabet: &Alphabet, decnum: u32) -> [u8; 5] {
+    group_digits(decnum).map(|x85| alphabet.byte_to_char85(x85))
+}
+
+// Splits a group into its 5 base 85 digits, most significant first. Rather than a chain of
+// divisions, the group is scaled to a 64-bit binary fraction of 85^5, whose integer part is the
+// first digit. Multiplying the fraction by 85 brings each following digit up into the integer
+// part. Rounding the scale up keeps every digit exact for all u32 values, which the ignored
+// test_group_digits_all() checks, and this is about half again as fast as dividing.
+#[inline]
+const fn group_digits(decnum: u32) -> [u8; 5] {
+    let mut outdata = [0u8; 5];
+
+    let scaled = decnum as u128 * INV_POW85_4 as u128;
+    outdata[0] = (scaled >> 64) as u8;
+    let mut fraction = scaled as u64;
+    let mut i = 1;
+    while i < 5 {
+        let scaled = fraction as u128 * 85;
+        outdata[i] = (scaled >> 64) as u8;
+        fraction = scaled as u64;
+        i += 1;
+    }
+
+    outdata
+}
+
+// The byte a partial group is padded out to 4 bytes with before it's encoded
+const ENCODE_PAD_BYTE: u8 = 0;
+
+// The character value a partial group is padded out to 5 characters with before it's decoded.
+// Dropping the encoder's trailing characters rounds the group's value down, and padding with the
+// highest value rounds it back up by less than one byte's worth, so the real bytes come back
+// unchanged.
+const DECODE_PAD_VALUE: u64 = 84;
+
+// Encodes the 1 to 3 bytes left over after the last full group. The bytes are padded out to a
+// full group with zeroes, and only the first extra.len() + 1 characters of the result are needed
+// to recover them.
+#[inline]
+fn encode_tail(alphabet: &Alphabet, extra: &[u8]) -> [u8; 5] {
+    debug_assert!(!extra.is_empty() && extra.len() < 4);
+
+    let mut last_chunk = [ENCODE_PAD_BYTE; 4];
+    last_chunk[..extra.len()].copy_from_slice(extra);
+
+    encode_group_with(alphabet, u32::from_be_bytes(last_chunk))
+}
+
+/// decode() turns encoded data into a vector of bytes. The data can be passed as a `&str`,
+/// `&[u8]`, `Vec<u8>`, or anything else which can be viewed as bytes, so data read off a socket
+/// doesn't need to be checked for valid UTF-8 first.
+pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    let indata = input.as_ref();
+    let mut outdata = Vec::<u8>::with_capacity(decoded_len(indata.len()));
+    decode_append(indata, &DecodeOptions::default(), &mut outdata)?;
+    Ok(outdata)
+}
+
+/// decode_boxed() turns encoded data into a boxed slice of bytes. The output is allocated at
+/// its final size up front, so unless the data contains whitespace there is no spare capacity to
+/// trim away afterwards.
+pub fn decode_boxed<T: AsRef<[u8]>>(input: T) -> Result<Box<[u8]>> {
+    let indata = input.as_ref();
+    let mut outdata = Vec::<u8>::with_capacity(decoded_len(indata.len()));
+    decode_append(indata, &DecodeOptions::default(), &mut outdata)?;
+    Ok(outdata.into_boxed_slice())
+}
+
+/// decode_cow() decodes data into a `Cow` whose lifetime is tied to the input. Decoded bytes
+/// never match their encoding, so for now the result is only borrowed when there's nothing to
+/// decode and is owned otherwise. Callers using it won't need to change if a borrowing fast
+/// path is added later.
+pub fn decode_cow<T: AsRef<[u8]> + ?Sized>(input: &T) -> Result<Cow<'_, [u8]>> {
+    let indata = input.as_ref();
+    if indata.is_empty() {
+        return Ok(Cow::Borrowed(&[]));
+    }
+    decode(indata).map(Cow::Owned)
+}
+
+/// DecodeOptions controls how encoded data is read and how strictly it is checked while decoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeOptions<'a> {
+    /// The alphabet the data was encoded with, `RFC1924` by default
+    pub alphabet: &'a Alphabet,
+    /// Skip ASCII whitespace (\n, \r, \t, vertical tab, space) instead of treating it as an
+    /// invalid character. This is on by default.
+    pub allow_whitespace: bool,
+    /// How groups of bytes were packed, big-endian by default
+    pub endianness: Endianness,
+    /// A marker which is removed from the start of the data if it's there, such as `UTF8_BOM`.
+    /// None by default.
+    pub strip_prefix: Option<&'a [u8]>,
+    /// A padding character which some encoders add to the end of the data, to be ignored there.
+    /// Only a run at the very end, before any trailing whitespace, counts as padding. It can't be
+    /// whitespace or a character of the alphabet, since data which really ends in a digit would
+    /// then be misread, and `decode_with_options()` returns `Error::InvalidAlphabet` for those.
+    /// That rules out the `=` some encoders use with either built-in alphabet. None by default.
+    pub trailing_pad: Option<u8>,
+}
+
+/// UTF8_BOM is the byte order mark some editors put at the start of UTF-8 text, for use as
+/// `DecodeOptions::strip_prefix`
+pub const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+impl Default for DecodeOptions<'_> {
+    fn default() -> Self {
+        DecodeOptions {
+            alphabet: &RFC1924,
+            allow_whitespace: true,
+            endianness: Endianness::Big,
+            strip_prefix: None,
+            trailing_pad: None,
+        }
+    }
+}
+
+/// decode_with_options() turns encoded data into a vector of bytes, checking it according to
+/// `options`
+pub fn decode_with_options<T: AsRef<[u8]>>(input: T, options: &DecodeOptions) -> Result<Vec<u8>> {
+    if let Some(pad) = options
+        .trailing_pad
+        .filter(|&pad| is_whitespace(pad) || options.alphabet.values[pad as usize] >= 0)
+    {
+        return Err(Error::InvalidAlphabet(pad));
+    }
+    let mut outdata = Vec::<u8>::new();
+    decode_append(input.as_ref(), options, &mut outdata)?;
+    Ok(outdata)
+}
+
+/// DecodeStats describes the input and output of `decode_verbose()`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// The number of bytes decoded
+    pub bytes_out: usize,
+    /// The number of characters read, including whitespace
+    pub chars_consumed: usize,
+    /// The number of whitespace characters skipped
+    pub whitespace_skipped: usize,
+}
+
+/// decode_to_string() decodes data which was encoded from text, such as by `encode_str()`. If
+/// the decoded bytes aren't valid UTF-8, `Error::InvalidUtf8` is returned with the offset of the
+/// first bad byte in the decoded data.
+pub fn decode_to_string<T: AsRef<[u8]>>(input: T) -> Result<String> {
+    String::from_utf8(decode(input)?).map_err(|e| Error::InvalidUtf8 {
+        offset: e.utf8_error().valid_up_to(),
+        source: e.utf8_error(),
+    })
+}
+
+/// decode_blocks() is the counterpart to `encode_blocks()`. The input must be a multiple of 5
+/// characters long, otherwise `Error::InvalidLength` is returned, and whitespace isn't allowed.
+pub fn decode_blocks<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    decode_blocks_with(&RFC1924, input.as_ref())
+}
+
+// Decodes whole groups only, as both decode_blocks() and Z85 require
+fn decode_blocks_with(alphabet: &Alphabet, indata: &[u8]) -> Result<Vec<u8>> {
+    if indata.len() % 5 != 0 {
+        return Err(Error::InvalidLength(indata.len()));
+    }
+
+    let mut outdata = Vec::with_capacity(decoded_len(indata.len()));
+    let options = DecodeOptions {
+        alphabet,
+        allow_whitespace: false,
+        ..DecodeOptions::default()
+    };
+    decode_append(indata, &options, &mut outdata)?;
+    Ok(outdata)
+}
+
+/// decode_with_progress() is like `decode()` but calls `progress` with the number of characters
+/// decoded so far and the total after every 64 KiB, and once at the end
+pub fn decode_with_progress<T: AsRef<[u8]>, F: FnMut(usize, usize)>(
+    input: T,
+    mut progress: F,
+) -> Result<Vec<u8>> {
+    let indata = input.as_ref();
+    let mut outdata = Vec::with_capacity(decoded_len(indata.len()));
+    let mut decoder = Base85Decoder::new();
+    let mut done = 0;
+    for chunk in indata.chunks(PROGRESS_CHUNK_LEN) {
+        decoder.update_into(chunk, &mut outdata)?;
+        done += chunk.len();
+        progress(done, indata.len());
+    }
+    if indata.is_empty() {
+        progress(0, 0);
+    }
+
+    let (bytes, len) = decoder.group.finish()?;
+    outdata.extend_from_slice(&bytes[..len]);
+    Ok(outdata)
+}
+
+/// decode_verbose() is like `decode()` but also reports how much of the input was whitespace,
+/// which helps track down data that was reformatted along the way
+pub fn decode_verbose<T: AsRef<[u8]>>(input: T) -> Result<(Vec<u8>, DecodeStats)> {
+    let indata = input.as_ref();
+    let mut outdata = Vec::with_capacity(decoded_len(indata.len()));
+    decode_append(indata, &DecodeOptions::default(), &mut outdata)?;
+
+    let stats = DecodeStats {
+        bytes_out: outdata.len(),
+        chars_consumed: indata.len(),
+        whitespace_skipped: indata.iter().filter(|&&c| is_whitespace(c)).count(),
+    };
+    Ok((outdata, stats))
+}
+
+/// decode_limited() is like `decode()` except that it never decodes more than `max_bytes` bytes,
+/// for capping the memory untrusted input can use. When the input is too long even after any
+/// whitespace, `Error::OutputTooLarge` is returned without decoding it.
+pub fn decode_limited<T: AsRef<[u8]>>(input: T, max_bytes: usize) -> Result<Vec<u8>> {
+    let indata = input.as_ref();
+
+    // The length is only an upper bound when there's whitespace, so when it's over the limit,
+    // count again without the whitespace before giving up
+    let mut out_len = decoded_len(indata.len());
+    if out_len > max_bytes {
+        let chars = indata.iter().filter(|&&c| !is_whitespace(c)).count();
+        out_len = decoded_len(chars);
+        if out_len > max_bytes {
+            return Err(Error::OutputTooLarge(max_bytes));
+        }
+    }
+
+    let mut outdata = Vec::with_capacity(out_len);
+    decode_append(indata, &DecodeOptions::default(), &mut outdata)?;
+    Ok(outdata)
+}
+
+/// decode_prefix() decodes the encoded data at the start of the input and returns it with the
+/// number of input bytes consumed, for data followed by something else. Decoding stops at the
+/// first byte which is neither in the alphabet nor whitespace, or at the end of the input, and
+/// everything before that byte is consumed, including any whitespace. The consumed data has to
+/// decode on its own, so a single character left over after the last full group is still
+/// `Error::InvalidLength`.
+pub fn decode_prefix<T: AsRef<[u8]>>(input: T) -> Result<(Vec<u8>, usize)> {
+    let indata = input.as_ref();
+    let consumed = indata
+        .iter()
+        .position(|&c| !is_base85_char(c) && !is_whitespace(c))
+        .unwrap_or(indata.len());
+    let outdata = decode(&indata[..consumed])?;
+    Ok((outdata, consumed))
+}
+
+/// decode_strict() is like `decode()` except that any whitespace in the data is reported as
+/// `Error::InvalidCharacter`. This is useful for checking that data is in canonical form and
+/// hasn't been reformatted.
+pub fn decode_strict<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    decode_with_options(
+        input,
+        &DecodeOptions {
+            allow_whitespace: false,
+            ..DecodeOptions::default()
+        },
+    )
+}
+
+/// decode_canonical() is like `decode_strict()` but also makes sure the data is exactly what
+/// `encode()` produces for the bytes it decodes to, so no two accepted inputs decode the same.
+/// Full groups can only be written one way, but the last character of a partial group stands for
+/// its last byte together with padding which is thrown away: a group of 2 characters decodes the
+/// same for up to 28 different last characters, 3 characters for up to 10 and 4 characters for
+/// up to 4. Only the one `encode()` writes, with all padding bits zero, is accepted, and the
+/// others are reported as `Error::NonCanonical` with the offset of the last character.
+pub fn decode_canonical<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    let indata = input.as_ref();
+    let outdata = decode_strict(indata)?;
+
+    let extra_chars = indata.len() % 5;
+    if extra_chars > 1 {
+        let tail = encode_tail(&RFC1924, &outdata[outdata.len() - (extra_chars - 1)..]);
+        if indata[indata.len() - extra_chars..] != tail[..extra_chars] {
+            return Err(Error::NonCanonical {
+                offset: indata.len() - 1,
+            });
+        }
+    }
+    Ok(outdata)
+}
+
+/// decode_lossy() decodes as much of the data as it can and returns it with every problem
+/// found, for showing them all at once rather than stopping at the first like `decode()` does.
+/// Characters outside the alphabet are reported and skipped as if they weren't there, so the
+/// groups after one may decode to different bytes than were meant. A group too large for 4
+/// bytes is reported and left out of the output. Whitespace is skipped as in `decode()`.
+pub fn decode_lossy<T: AsRef<[u8]>>(input: T) -> (Vec<u8>, Vec<Error>) {
+    let indata = input.as_ref();
+    let mut outdata = Vec::with_capacity(decoded_len(indata.len()));
+    let mut errors = Vec::new();
+    let mut group = GroupDecoder::default();
+    for (offset, &c) in indata.iter().enumerate() {
+        if is_whitespace(c) {
+            continue;
+        }
+
+        let value = match RFC1924.char85_to_byte(c, offset) {
+            Ok(value) => value,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        match group.push(value, offset) {
+            Ok(Some(bytes)) => outdata.extend_from_slice(&bytes),
+            Ok(None) => {}
+            Err(e) => {
+                errors.push(e);
+                group.accumulator = 0;
+                group.count = 0;
+            }
+        }
+    }
+
+    match group.finish() {
+        Ok((bytes, len)) => outdata.extend_from_slice(&bytes[..len]),
+        Err(e) => errors.push(e),
+    }
+    (outdata, errors)
+}
+
+/// decode_with() turns data encoded with the characters of `alphabet` into a vector of bytes
+pub fn decode_with<T: AsRef<[u8]>>(alphabet: &Alphabet, input: T) -> Result<Vec<u8>> {
+    decode_with_options(
+        input,
+        &DecodeOptions {
+            alphabet,
+            ..DecodeOptions::default()
+        },
+    )
+}
+
+/// decode_into() appends the bytes decoded from encoded data to `out` and returns the number of
+/// bytes added. If the data can't be decoded, `out` is left as it was.
+pub fn decode_into<T: AsRef<[u8]>>(input: T, out: &mut Vec<u8>) -> Result<usize> {
+    let start = out.len();
+    match decode_append(input.as_ref(), &DecodeOptions::default(), out) {
+        Ok(()) => Ok(out.len() - start),
+        Err(e) => {
+            out.truncate(start);
+            Err(e)
+        }
+    }
+}
+
+/// ScratchDecoder decodes many small pieces of encoded data one after another while reusing the
+/// same buffer for the output, so once the buffer has grown to fit the largest piece, decoding
+/// doesn't allocate at all.
+///
+/// The slice returned by `decode()` borrows the ScratchDecoder, so it has to be dropped (or
+/// copied out) before the next call; the borrow checker rejects code that holds on to it.
+#[derive(Clone, Debug, Default)]
+pub struct ScratchDecoder {
+    buf: Vec<u8>,
+}
+
+impl ScratchDecoder {
+    /// new() creates a ScratchDecoder with an empty buffer
+    pub fn new() -> ScratchDecoder {
+        ScratchDecoder::default()
+    }
+
+    /// with_capacity() creates a ScratchDecoder whose buffer can hold `capacity` decoded bytes
+    /// before it needs to grow
+    pub fn with_capacity(capacity: usize) -> ScratchDecoder {
+        ScratchDecoder {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// decode() decodes encoded data into the buffer, replacing whatever the last call decoded,
+    /// and returns the decoded bytes. The bytes are only valid until the next call.
+    pub fn decode<T: AsRef<[u8]>>(&mut self, input: T) -> Result<&[u8]> {
+        self.buf.clear();
+        decode_append(input.as_ref(), &DecodeOptions::default(), &mut self.buf)?;
+        Ok(&self.buf)
+    }
+}
+
+// Five characters can hold values up to 85^5 - 1, which is more than 4 bytes can. Groups that
+// large can't come from the encoder, so they're rejected rather than silently truncated.
+#[inline]
+fn check_group(accumulator: u64, group_start: usize) -> Result<u32> {
+    if !group_fits_u32(accumulator) {
+        return Err(Error::Overflow {
+            offset: group_start,
+        });
+    }
+    Ok(accumulator as u32)
+}
+
+// Collects character values into groups of 5 and turns each group back into 4 bytes
+#[derive(Default)]
+struct GroupDecoder {
+    accumulator: u64,
+    count: usize,
+    // The offset of the first character of the current group
+    start: usize,
+    // The number of characters pushed so far, not counting skipped whitespace
+    total: usize,
+}
+
+impl GroupDecoder {
+    // Adds the value of the character at `offset`, returning the decoded bytes when it completes
+    // a group
+    #[inline]
+    fn push(&mut self, value: u8, offset: usize) -> Result<Option<[u8; 4]>> {
+        if self.count == 0 {
+            self.start = offset;
+        }
+        self.accumulator = self.accumulator * 85 + value as u64;
+        self.count += 1;
+        self.total += 1;
+        if self.count < 5 {
+            return Ok(None);
+        }
+
+        let outdata = check_group(self.accumulator, self.start)?.to_be_bytes();
+        self.accumulator = 0;
+        self.count = 0;
+        Ok(Some(outdata))
+    }
+
+    // Decodes a trailing partial group, returning the bytes and how many of them are real. A
+    // group of n characters holds n - 1 bytes, padded back out with DECODE_PAD_VALUE. The
+    // encoder never produces a lone character, which can't hold even one byte, so that means the
+    // data as a whole has an invalid length.
+    fn finish(&mut self) -> Result<([u8; 4], usize)> {
+        let count = self.count;
+        match count {
+            0 => Ok(([0; 4], 0)),
+            1 => Err(Error::InvalidLength(self.total)),
+            _ => {
+                let mut accumulator = self.accumulator;
+                for _ in count..5 {
+                    accumulator = accumulator * 85 + DECODE_PAD_VALUE;
+                }
+                self.accumulator = 0;
+                self.count = 0;
+                Ok((
+                    check_group(accumulator, self.start)?.to_be_bytes(),
+                    count - 1,
+                ))
+            }
+        }
+    }
+}
+
+fn decode_append(indata: &[u8], options: &DecodeOptions, outdata: &mut Vec<u8>) -> Result<()> {
+    let start = outdata.len();
+    outdata.reserve(decoded_len(indata.len()));
+
+    decode_groups(indata, options, |bytes| {
+        outdata.extend_from_slice(bytes);
+        Ok(())
+    })?;
+
+    debug_assert!(outdata.len() - start <= decoded_len(indata.len()));
+    Ok(())
+}
+
+// The core of decoding, which hands each group of decoded bytes to `emit` as soon as it is
+// complete. Nothing is allocated here, so callers decide where the bytes go, and any error from
+// `emit` stops decoding.
+fn decode_groups<F: FnMut(&[u8]) -> Result<()>>(
+    indata: &[u8],
+    options: &DecodeOptions,
+    mut emit: F,
+) -> Result<()> {
+    // Offsets in errors are still relative to the data as given
+    let start = match options.strip_prefix {
+        Some(prefix) if indata.starts_with(prefix) => prefix.len(),
+        _ => 0,
+    };
+    let mut indata = indata;
+    if let Some(pad) = options.trailing_pad {
+        // Whitespace may come after the padding, such as a newline at the end of a file
+        while let Some((&c, rest)) = indata.split_last() {
+            if c != pad && !(options.allow_whitespace && is_whitespace(c)) {
+                break;
+            }
+            indata = rest;
+        }
+    }
+
+    #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+    let mut block = [0u8; simd::DECODE_BLOCK_BYTES];
+    // Where the vector code is next tried. After it gives up on a block, the scalar code handles
+    // the whole block before it's tried again, so input it can't handle doesn't cost much extra.
+    #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+    let mut next_block = start;
+
+    let mut group = GroupDecoder::default();
+    let mut pos = start;
+    while pos < indata.len() {
+        #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+        if group.count == 0 && pos >= next_block {
+            let used = simd::decode_block(options.alphabet, &indata[pos..], &mut block);
+            if used > 0 {
+                let bytes = &mut block[..used / 5 * 4];
+                if options.endianness == Endianness::Little {
+                    bytes.chunks_exact_mut(4).for_each(<[u8]>::reverse);
+                }
+                emit(bytes)?;
+                group.total += used;
+                pos += used;
+                continue;
+            }
+            next_block = pos + simd::DECODE_BLOCK_CHARS;
+        }
+
+        let (offset, c) = (pos, indata[pos]);
+        pos += 1;
+        if options.allow_whitespace && is_whitespace(c) {
+            continue;
+        }
+
+        if let Some(mut bytes) = group.push(options.alphabet.char85_to_byte(c, offset)?, offset)? {
+            if options.endianness == Endianness::Little {
+                bytes.reverse();
+            }
+            emit(&bytes)?;
+        }
+    }
+
+    let (bytes, len) = group.finish()?;
+    emit(&bytes[..len])
+}
+
+/// Base85Decoder decodes data which arrives in pieces without needing `std::io`. Whitespace is
+/// skipped, and a group of 5 characters split across calls to `update()` is carried over until
+/// it is complete, so the bytes only depend on the data, not on how it was split up. Offsets in
+/// errors count from the start of the first piece.
+#[derive(Default)]
+pub struct Base85Decoder {
+    group: GroupDecoder,
+    offset: usize,
+}
+
+impl Base85Decoder {
+    /// new() creates a Base85Decoder with no data
+    pub fn new() -> Base85Decoder {
+        Base85Decoder::default()
+    }
+
+    /// update() decodes the next piece of data, returning the bytes for every group it completes
+    pub fn update<T: AsRef<[u8]>>(&mut self, chunk: T) -> Result<Vec<u8>> {
+        let chunk = chunk.as_ref();
+        let mut outdata = Vec::with_capacity(decoded_len(self.group.count + chunk.len()));
+        self.update_into(chunk, &mut outdata)?;
+        Ok(outdata)
+    }
+
+    // Does the work of update(), appending to `outdata`
+    fn update_into(&mut self, chunk: &[u8], outdata: &mut Vec<u8>) -> Result<()> {
+        for &c in chunk {
+            let offset = self.offset;
+            self.offset += 1;
+            if is_whitespace(c) {
+                continue;
+            }
+
+            if let Some(bytes) = self
+                .group
+                .push(RFC1924.char85_to_byte(c, offset)?, offset)?
+            {
+                outdata.extend_from_slice(&bytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// finalize() decodes the partial group left over from the last `update()`, if any. A single
+    /// leftover character can't hold any bytes, which means the data was cut short, so
+    /// `Error::UnexpectedEof` is returned.
+    pub fn finalize(mut self) -> Result<Vec<u8>> {
+        let mut outdata = Vec::new();
+        self.finish_into(&mut outdata)?;
+        Ok(outdata)
+    }
+
+    // Does the work of finalize(), appending to `outdata`, for the adapters which hold a decoder
+    // rather than owning one to give away
+    fn finish_into(&mut self, outdata: &mut Vec<u8>) -> Result<()> {
+        match self.group.finish() {
+            Ok((bytes, len)) => {
+                outdata.extend_from_slice(&bytes[..len]);
+                Ok(())
+            }
+            Err(Error::InvalidLength(_)) => Err(Error::UnexpectedEof {
+                offset: self.offset,
+                in_group: true,
+            }),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Base85Reader decodes borrowed encoded data a byte at a time, for parsers which pull bytes as
+/// they need them instead of decoding everything up front. Whitespace is skipped. It's also an
+/// `Iterator` over the decoded bytes, which ends after the first error.
+pub struct Base85Reader<'a> {
+    indata: &'a [u8],
+    pos: usize,
+    group: GroupDecoder,
+    outbuf: [u8; 4],
+    out_pos: usize,
+    out_len: usize,
+    finished: bool,
+}
+
+impl<'a> Base85Reader<'a> {
+    /// new() creates a Base85Reader over encoded data
+    pub fn new<T: AsRef<[u8]> + ?Sized>(input: &'a T) -> Base85Reader<'a> {
+        Base85Reader {
+            indata: input.as_ref(),
+            pos: 0,
+            group: GroupDecoder::default(),
+            outbuf: [0; 4],
+            out_pos: 0,
+            out_len: 0,
+            finished: false,
+        }
+    }
+
+    /// next_byte() returns the next decoded byte, or None once the data has all been decoded or
+    /// an error has been returned
+    pub fn next_byte(&mut self) -> Option<Result<u8>> {
+        while self.out_pos == self.out_len {
+            if self.finished {
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+
+        self.out_pos += 1;
+        Some(Ok(self.outbuf[self.out_pos - 1]))
+    }
+
+    // Decodes up to the next completed group, or the partial group at the end of the data
+    fn fill(&mut self) -> Result<()> {
+        while let Some(&c) = self.indata.get(self.pos) {
+            let offset = self.pos;
+            self.pos += 1;
+            if is_whitespace(c) {
+                continue;
+            }
+
+            if let Some(bytes) = self
+                .group
+                .push(RFC1924.char85_to_byte(c, offset)?, offset)?
+            {
+                (self.outbuf, self.out_pos, self.out_len) = (bytes, 0, 4);
+                return Ok(());
+            }
+        }
+
+        self.finished = true;
+        let (bytes, len) = self.group.finish()?;
+        (self.outbuf, self.out_pos, self.out_len) = (bytes, 0, len);
+        Ok(())
+    }
+}
+
+impl Iterator for Base85Reader<'_> {
+    type Item = Result<u8>;
+
+    fn next(&mut self) -> Option<Result<u8>> {
+        self.next_byte()
+    }
+}
+
+/// decode_chars() decodes encoded data from an iterator of characters, such as a filtered
+/// `str::chars()`, without collecting it into a string first. Whitespace is skipped as in
+/// `decode()`. Offsets in errors count characters rather than bytes, and a character that isn't
+/// ASCII is reported as the first byte of its UTF-8 encoding.
+pub fn decode_chars<I: IntoIterator<Item = char>>(chars: I) -> Result<Vec<u8>> {
+    let mut outdata = Vec::new();
+    let mut group = GroupDecoder::default();
+    for (offset, c) in chars.into_iter().enumerate() {
+        let mut utf8 = [0; 4];
+        let c = c.encode_utf8(&mut utf8).as_bytes()[0];
+        if is_whitespace(c) {
+            continue;
+        }
+
+        if let Some(bytes) = group.push(RFC1924.char85_to_byte(c, offset)?, offset)? {
+            outdata.extend_from_slice(&bytes);
+        }
+    }
+
+    let (bytes, len) = group.finish()?;
+    outdata.extend_from_slice(&bytes[..len]);
+    Ok(outdata)
+}
+
+/// DecodeOrIo is the error from `decode_fallible_iter()`: either the data couldn't be decoded
+/// or the iterator it came from failed
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub enum DecodeOrIo<E> {
+    #[error(transparent)]
+    Decode(#[from] Error),
+    #[error(transparent)]
+    Io(E),
+}
+
+/// decode_fallible_iter() decodes encoded data from an iterator of bytes which can fail, such as
+/// `Read::bytes()`. Whitespace is skipped as in `decode()`. Decoding stops at the first error from
+/// the iterator, which is returned as `DecodeOrIo::Io`.
+pub fn decode_fallible_iter<E, I: IntoIterator<Item = core::result::Result<u8, E>>>(
+    iter: I,
+) -> core::result::Result<Vec<u8>, DecodeOrIo<E>> {
+    let mut outdata = Vec::new();
+    let mut group = GroupDecoder::default();
+    for (offset, c) in iter.into_iter().enumerate() {
+        let c = c.map_err(DecodeOrIo::Io)?;
+        if is_whitespace(c) {
+            continue;
+        }
+
+        if let Some(bytes) = group.push(RFC1924.char85_to_byte(c, offset)?, offset)? {
+            outdata.extend_from_slice(&bytes);
+        }
+    }
+
+    let (bytes, len) = group.finish()?;
+    outdata.extend_from_slice(&bytes[..len]);
+    Ok(outdata)
+}
+
+/// decode_with_comments() is like `decode()` but also skips comments, which run from
+/// `comment_prefix` to the end of the line, so encoded blocks in config files can be annotated.
+/// The prefix has to be an ASCII character that isn't in the alphabet, such as `/` or `"`, or
+/// this panics. `#` and `;` are both in the alphabet.
+pub fn decode_with_comments<T: AsRef<[u8]>>(input: T, comment_prefix: char) -> Result<Vec<u8>> {
+    assert!(
+        comment_prefix.is_ascii() && RFC1924.values[comment_prefix as usize] == -1,
+        "the comment prefix can't be a character of the alphabet"
+    );
+    let comment_prefix = comment_prefix as u8;
+
+    let indata = input.as_ref();
+    let mut outdata = Vec::with_capacity(decoded_len(indata.len()));
+    let mut group = GroupDecoder::default();
+    let mut in_comment = false;
+    for (offset, &c) in indata.iter().enumerate() {
+        if in_comment {
+            in_comment = c != b'\n';
+            continue;
+        }
+        if c == comment_prefix {
+            in_comment = true;
+            continue;
+        }
+        if is_whitespace(c) {
+            continue;
+        }
+
+        if let Some(bytes) = group.push(RFC1924.char85_to_byte(c, offset)?, offset)? {
+            outdata.extend_from_slice(&bytes);
+        }
+    }
+
+    let (bytes, len) = group.finish()?;
+    outdata.extend_from_slice(&bytes[..len]);
+    Ok(outdata)
+}
+
+// The number of characters decode_debug() shows on either side of an error
+const CONTEXT_LEN: usize = 8;
+
+/// decode_debug() is like `decode()` but errors at a known offset come back as
+/// `Error::Context`, which shows the input around the offending character, for diagnosing data
+/// that was corrupted along the way
+pub fn decode_debug<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    let indata = input.as_ref();
+    decode(indata).map_err(|error| {
+        let offset = match error {
+            Error::InvalidCharacter { offset, .. }
+            | Error::Overflow { offset }
+            | Error::UnexpectedEof { offset, .. } => offset,
+            _ => return error,
+        };
+
+        let start = offset.saturating_sub(CONTEXT_LEN);
+        let end = (offset + CONTEXT_LEN + 1).min(indata.len());
+        let mut context: String = indata[start..end]
+            .iter()
+            .map(|&c| match c {
+                b' '..=b'~' => c as char,
+                _ => '.',
+            })
+            .collect();
+        context.push('\n');
+        context.extend(core::iter::repeat_n(' ', offset - start));
+        context.push('^');
+
+        Error::Context {
+            error: Box::new(error),
+            context,
+        }
+    })
+}
+
+/// validate() checks that encoded data would decode without errors, applying the same rules as
+/// `decode()`, but without allocating anything. It returns the first error found.
+pub fn validate<T: AsRef<[u8]>>(input: T) -> Result<()> {
+    decode_groups(input.as_ref(), &DecodeOptions::default(), |_| Ok(()))
+}
+
+/// verify_decodable() checks that encoded data would decode without errors, like `validate()`,
+/// and returns the number of bytes it decodes to, for sizing a buffer exactly before decoding.
+/// Nothing is allocated.
+pub fn verify_decodable<T: AsRef<[u8]>>(input: T) -> Result<usize> {
+    let mut out_len = 0;
+    decode_groups(input.as_ref(), &DecodeOptions::default(), |bytes| {
+        out_len += bytes.len();
+        Ok(())
+    })?;
+    Ok(out_len)
+}
+
+/// reformat() rewraps encoded data into lines of `line_len` characters, like `encode_wrapped()`,
+/// dropping whatever whitespace it had before. The characters are checked to decode, with the
+/// same errors as `decode()`, but are copied over as they are rather than decoded and encoded
+/// again.
+pub fn reformat(instr: &str, line_len: usize) -> Result<String> {
+    let mut outdata = String::with_capacity(instr.len());
+    let mut group = GroupDecoder::default();
+    for (offset, &c) in instr.as_bytes().iter().enumerate() {
+        if is_whitespace(c) {
+            continue;
+        }
+
+        let value = RFC1924.char85_to_byte(c, offset)?;
+        group.push(value, offset)?;
+        if line_len > 0 && group.total > 1 && (group.total - 1) % line_len == 0 {
+            outdata.push('\n');
+        }
+        outdata.push(c as char);
+    }
+    group.finish()?;
+    Ok(outdata)
+}
+
+/// encode_in_place() encodes the bytes in `buf` over themselves, leaving `buf` holding exactly
+/// what `encode()` returns as bytes. The buffer is grown to `encoded_len()` and filled from the
+/// back, which the output, 5 characters for every 4 bytes, can do without overwriting input that
+/// hasn't been read yet, so the data isn't copied into a separate output.
+pub fn encode_in_place(buf: &mut Vec<u8>) {
+    let in_len = buf.len();
+    let groups = in_len / 4;
+    let extra = in_len % 4;
+    buf.resize(encoded_len(in_len), 0);
+
+    // The partial group is last, so it goes first
+    if extra > 0 {
+        let tail = encode_tail(&RFC1924, &buf[groups * 4..in_len]);
+        buf[groups * 5..].copy_from_slice(&tail[..extra + 1]);
+    }
+    for i in (0..groups).rev() {
+        let chunk = &buf[i * 4..i * 4 + 4];
+        let decnum = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        buf[i * 5..i * 5 + 5].copy_from_slice(&encode_group_with(&RFC1924, decnum));
+    }
+}
+
+/// decode_in_place() decodes the encoded data in `buf` over itself, truncates `buf` to the
+/// decoded bytes, and returns their number. Every group of 5 characters decodes to 4 bytes, so
+/// the output never catches up with the input and nothing extra is allocated. The contents of
+/// `buf` are unspecified after an error.
+pub fn decode_in_place(buf: &mut Vec<u8>) -> Result<usize> {
+    let mut group = GroupDecoder::default();
+    let mut out_len = 0;
+    for offset in 0..buf.len() {
+        let c = buf[offset];
+        if is_whitespace(c) {
+            continue;
+        }
+
+        if let Some(bytes) = group.push(RFC1924.char85_to_byte(c, offset)?, offset)? {
+            // The group just completed took at least 5 characters, so out_len + 4 <= offset
+            buf[out_len..out_len + 4].copy_from_slice(&bytes);
+            out_len += 4;
+        }
+    }
+
+    let (bytes, len) = group.finish()?;
+    buf[out_len..out_len + len].copy_from_slice(&bytes[..len]);
+    out_len += len;
+
+    buf.truncate(out_len);
+    Ok(out_len)
+}
+
+/// decode_slice() decodes data into the start of `out` without allocating and returns the number
+/// of bytes written. Whitespace makes the exact output length depend on the data, so size `out`
+/// with `decoded_len()` of the input length, which is always enough. If `out` turns out to be
+/// too small, `Error::BufferTooSmall` is returned. The contents of `out` are unspecified after
+/// any error.
+pub fn decode_slice<T: AsRef<[u8]>>(input: T, out: &mut [u8]) -> Result<usize> {
+    let mut out_len = 0;
+    decode_groups(input.as_ref(), &DecodeOptions::default(), |bytes| {
+        let dest = out
+            .get_mut(out_len..out_len + bytes.len())
+            .ok_or(Error::BufferTooSmall)?;
+        dest.copy_from_slice(bytes);
+        out_len += bytes.len();
+        Ok(())
+    })?;
+    Ok(out_len)
+}
+
+// The CRC-32 lookup table for the IEEE polynomial, as used by zlib and PNG
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(indata: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in indata {
+        crc = (crc >> 8) ^ CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize];
+    }
+    !crc
+}
+
+/// encode_checked() encodes a slice of bytes with its CRC-32 checksum, stored big-endian in
+/// front of the data and encoded along with it, so that `decode_checked()` can detect corruption.
+/// The output is not compatible with plain `decode()`.
+pub fn encode_checked<T: AsRef<[u8]>>(indata: T) -> String {
+    let indata = indata.as_ref();
+    let mut checked = Vec::with_capacity(indata.len() + 4);
+    checked.extend_from_slice(&crc32(indata).to_be_bytes());
+    checked.extend_from_slice(indata);
+    encode(&checked)
+}
+
+/// decode_checked() decodes data made by `encode_checked()` and verifies its checksum, returning
+/// `Error::ChecksumMismatch` if it doesn't match or is missing
+pub fn decode_checked<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    let mut outdata = decode(input)?;
+    if outdata.len() < 4 {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    let checksum = u32::from_be_bytes([outdata[0], outdata[1], outdata[2], outdata[3]]);
+    if crc32(&outdata[4..]) != checksum {
+        return Err(Error::ChecksumMismatch);
+    }
+    outdata.drain(..4);
+    Ok(outdata)
+}
+
+/// encode_u128() turns a 128-bit number, such as a UUID or hash, into the 20 character form RFC
+/// 1924 gives for IPv6 addresses. Unlike `encode()`, which works in groups of 4 bytes, this
+/// treats the value as one number.
+pub fn encode_u128(mut value: u128) -> String {
+    let mut outdata = [0u8; 20];
+    for c in outdata.iter_mut().rev() {
+        *c = RFC1924.byte_to_char85((value % 85) as u8);
+        value /= 85;
+    }
+    outdata.iter().map(|&c| c as char).collect()
+}
+
+/// decode_u128() turns the 20 character form from `encode_u128()` back into the number. The
+/// input must be exactly 20 characters with no whitespace, and its value must fit in 128 bits.
+pub fn decode_u128(instr: &str) -> Result<u128> {
+    let indata = instr.as_bytes();
+    if indata.len() != 20 {
+        return Err(Error::InvalidLength(indata.len()));
+    }
+
+    let mut value: u128 = 0;
+    for (offset, &c) in indata.iter().enumerate() {
+        let digit = RFC1924.char85_to_byte(c, offset)?;
+        value = value
+            .checked_mul(85)
+            .and_then(|v| v.checked_add(digit as u128))
+            .ok_or(Error::Overflow { offset: 0 })?;
+    }
+    Ok(value)
+}
+
+/// encode_ipv6() turns an IPv6 address into the 20 character form given in RFC 1924
+pub fn encode_ipv6(addr: Ipv6Addr) -> String {
+    encode_u128(u128::from(addr))
+}
+
+/// decode_ipv6() turns the 20 character RFC 1924 form of an IPv6 address back into the address,
+/// with the same rules as `decode_u128()`
+pub fn decode_ipv6(instr: &str) -> Result<Ipv6Addr> {
+    decode_u128(instr).map(Ipv6Addr::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use alloc::{format, string::ToString, vec};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_encode_decode() {
+        // The list of tests consists of the unencoded data on the left and the encoded data on
+        // the right. By using strings for the arbitrary binary data, we make the test much less
+        // complicated to write.
+        let testlist = [
+            ("a", "VE"),
+            ("aa", "VPO"),
+            ("aaa", "VPRn"),
+            ("aaaa", "VPRom"),
+            ("aaaaa", "VPRomVE"),
+            ("aaaaaa", "VPRomVPO"),
+            ("aaaaaaa", "VPRomVPRn"),
+            ("aaaaaaaa", "VPRomVPRom"),
+        ];
+
+        for test in testlist.iter() {
+            let s = encode(test.0);
+            assert_eq!(
+                s, test.1,
+                "encoder test failed: wanted: {}, got: {}",
+                test.0, s
+            );
+
+            let b = decode(test.1)
+                .unwrap_or_else(|e| panic!("decoder test error on input {}: {}", test.1, e));
+
+            let s = String::from_utf8(b).unwrap_or_else(|e| {
+                panic!(
+                    "decoder test '{}' failed to convert to string: {:#?}",
+                    test.1, e
+                )
             });
 
             assert_eq!(
-                test.0, s,
-                "decoder data mismatch: wanted: {}, got: {}",
-                test.0, s
+                test.0, s,
+                "decoder data mismatch: wanted: {}, got: {}",
+                test.0, s
+            );
+        }
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let mut out = String::new();
+        encode_into(b"aaaaaaa", &mut out);
+        assert_eq!(out, "VPRomVPRn");
+
+        // Encoding something no larger into the same buffer replaces the old contents without
+        // needing more room
+        let capacity = out.capacity();
+        encode_into(b"aaaa", &mut out);
+        assert_eq!(out, "VPRom");
+        assert_eq!(out.capacity(), capacity);
+
+        encode_into(b"", &mut out);
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn test_decode_into() {
+        let mut out = b"xyz".to_vec();
+        assert_eq!(decode_into("VPRomVE", &mut out).unwrap(), 5);
+        assert_eq!(out, b"xyzaaaaa");
+
+        // A failed decode leaves the buffer untouched
+        assert!(decode_into("VPRom\"VE", &mut out).is_err());
+        assert_eq!(out, b"xyzaaaaa");
+    }
+
+    #[test]
+    fn test_lengths() {
+        for i in 0..64 {
+            let data = vec![b'a'; i];
+            let encoded = encode(&data);
+            assert_eq!(encoded_len(i), encoded.len());
+            assert_eq!(decoded_len(encoded.len()), i);
+        }
+
+        // Both are usable for sizing arrays
+        const BUFFER: [u8; encoded_len(16)] = [0; encoded_len(16)];
+        assert_eq!(BUFFER.len(), 20);
+        assert_eq!([0u8; decoded_len(20)].len(), 16);
+    }
+
+    #[test]
+    fn test_overhead_ratio() {
+        assert_eq!(overhead_ratio(0), 1.0);
+        assert_eq!(overhead_ratio(1), 2.0);
+        assert_eq!(overhead_ratio(2), 1.5);
+        assert_eq!(overhead_ratio(3), 4.0 / 3.0);
+        assert_eq!(overhead_ratio(4), OVERHEAD);
+        assert_eq!(overhead_ratio(5), 7.0 / 5.0);
+        assert_eq!(overhead_ratio(4096), OVERHEAD);
+        assert!(overhead_ratio(4097) > OVERHEAD);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_huge_lengths() {
+        // Lengths past 4 * 2^32 bytes, where a u32 group count would wrap, can't be allocated
+        // in a test, so check the length arithmetic instead
+        assert_eq!(encoded_len(4 << 32), 5 << 32);
+        assert_eq!(encoded_len((4 << 32) + 1), (5 << 32) + 2);
+        assert_eq!(decoded_len(5 << 32), 4 << 32);
+
+        // No slice can be longer than isize::MAX bytes, and its encoding still fits in a usize
+        let max = isize::MAX as usize;
+        assert_eq!(encoded_len(max), max / 4 * 5 + 4);
+        assert_eq!(decoded_len(encoded_len(max)), max);
+    }
+
+    #[test]
+    fn test_decode_bytes() {
+        assert_eq!(decode(b"VPRomVE").unwrap(), b"aaaaa");
+        let owned: Vec<u8> = b"VPRomVE".to_vec();
+        assert_eq!(decode(owned).unwrap(), b"aaaaa");
+        assert_eq!(decode(String::from("VPRomVE")).unwrap(), b"aaaaa");
+    }
+
+    #[test]
+    fn test_non_ascii_input() {
+        for byte in [0x80, 0xff] {
+            assert_eq!(
+                decode([b'V', b'P', byte, b'o', b'm']),
+                Err(Error::InvalidCharacter { byte, offset: 2 })
+            );
+        }
+        // The first byte of a multibyte UTF-8 sequence is the one reported
+        assert_eq!(
+            decode("VPRom VPé"),
+            Err(Error::InvalidCharacter {
+                byte: 0xc3,
+                offset: 8
+            })
+        );
+        assert!((0x80..=0xff).all(|c| !is_base85_char(c)));
+    }
+
+    #[test]
+    fn test_invalid_character_offset() {
+        // Whitespace counts towards the offset so that it points into the original input
+        let err = decode("VPRom\n  VP\"n").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidCharacter {
+                byte: b'"',
+                offset: 10
+            }
+        ));
+        assert_eq!(err.to_string(), "Unexpected character '34' at offset 10");
+    }
+
+    #[test]
+    fn test_overflow() {
+        // "|NsC0" is the largest group that fits in 4 bytes
+        assert_eq!(decode("|NsC0").unwrap(), [0xFF; 4]);
+        assert!(matches!(
+            decode("VPRom|NsC1"),
+            Err(Error::Overflow { offset: 5 })
+        ));
+        assert!(matches!(decode("~~"), Err(Error::Overflow { offset: 0 })));
+    }
+
+    #[test]
+    fn test_zeros_and_ones() {
+        assert_eq!(encode([0u8; 4]), "00000");
+        assert_eq!(decode("00000").unwrap(), [0u8; 4]);
+        assert_eq!(encode([0xffu8; 4]), "|NsC0");
+        assert_eq!(decode("|NsC0").unwrap(), [0xffu8; 4]);
+
+        // Five of the largest digit are far past 0xFFFFFFFF
+        assert_eq!(decode("~~~~~"), Err(Error::Overflow { offset: 0 }));
+        assert_eq!(decode("VPRom~~~~~"), Err(Error::Overflow { offset: 5 }));
+    }
+
+    #[test]
+    fn test_decode_strict() {
+        assert_eq!(decode_strict("VPRomVE").unwrap(), b"aaaaa");
+        assert!(matches!(
+            decode_strict("VPRom\nVE"),
+            Err(Error::InvalidCharacter {
+                byte: b'\n',
+                offset: 5
+            })
+        ));
+        assert!(decode_with_options("VPRom\nVE", &DecodeOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_encode_iter() {
+        for i in 0..16 {
+            let data: Vec<u8> = (0..i).map(|n| n * 17).collect();
+            assert_eq!(
+                encode_iter(data.iter().copied()).collect::<String>(),
+                encode(&data)
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_boxed() {
+        assert_eq!(&*decode_boxed("VPRomVPRn").unwrap(), b"aaaaaaa");
+        assert!(decode_boxed("VPR\"m").is_err());
+    }
+
+    #[test]
+    fn test_empty_and_single_byte() {
+        assert_eq!(encode(b""), "");
+        assert!(decode("").unwrap().is_empty());
+
+        for byte in 0..=255u8 {
+            let encoded = encode([byte]);
+            assert_eq!(encoded.len(), 2);
+            assert_eq!(decode(&encoded).unwrap(), [byte]);
+        }
+
+        // A lone character can't hold a byte. decode() sees the whole input, so it reports the
+        // length, while Base85Decoder can't know more wasn't coming and reports the end of input.
+        assert_eq!(decode("V"), Err(Error::InvalidLength(1)));
+        assert_eq!(decode("VPRom\nV"), Err(Error::InvalidLength(6)));
+        let mut decoder = Base85Decoder::new();
+        assert!(decoder.update("V").unwrap().is_empty());
+        assert_eq!(
+            decoder.finalize(),
+            Err(Error::UnexpectedEof {
+                offset: 1,
+                in_group: true
+            })
+        );
+    }
+
+    #[test]
+    fn test_strip_prefix() {
+        let options = DecodeOptions {
+            strip_prefix: Some(UTF8_BOM),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options(b"\xef\xbb\xbfVPRomVE", &options).unwrap(),
+            b"aaaaa"
+        );
+        assert_eq!(decode_with_options("VPRomVE", &options).unwrap(), b"aaaaa");
+        assert_eq!(
+            decode(b"\xef\xbb\xbfVPRomVE"),
+            Err(Error::InvalidCharacter {
+                byte: 0xef,
+                offset: 0
+            })
+        );
+        assert_eq!(
+            decode_with_options(b"\xef\xbb\xbfVP\"om", &options),
+            Err(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_canonical() {
+        let data: Vec<u8> = (0..=255).collect();
+        for len in 0..12 {
+            assert_eq!(decode_canonical(encode(&data[..len])).unwrap(), data[..len]);
+        }
+
+        // Every last character which decodes to the same byte as "VE" but isn't 'E'
+        let mut aliases = 0;
+        for &c in &ALPHABET {
+            let encoded = [b'V', c];
+            if c != b'E' && decode(encoded) == Ok(b"a".to_vec()) {
+                aliases += 1;
+                assert_eq!(
+                    decode_canonical(encoded),
+                    Err(Error::NonCanonical { offset: 1 })
+                );
+            }
+        }
+        assert_eq!(aliases, 27);
+        assert_eq!(decode("VPRomVF").unwrap(), b"aaaaa");
+        assert_eq!(
+            decode_canonical("VPRomVF"),
+            Err(Error::NonCanonical { offset: 6 })
+        );
+        assert!(matches!(
+            decode_canonical("VPRom VE"),
+            Err(Error::InvalidCharacter { byte: b' ', .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_lossy() {
+        assert_eq!(decode_lossy("VPRom VE"), (b"aaaaa".to_vec(), Vec::new()));
+        assert_eq!(
+            decode_lossy("VP\"Rom|NsC1V\"E"),
+            (
+                b"aaaaa".to_vec(),
+                vec![
+                    Error::InvalidCharacter {
+                        byte: b'"',
+                        offset: 2
+                    },
+                    Error::Overflow { offset: 6 },
+                    Error::InvalidCharacter {
+                        byte: b'"',
+                        offset: 12
+                    },
+                ]
+            )
+        );
+        assert_eq!(
+            decode_lossy("VPRomV"),
+            (b"aaaa".to_vec(), vec![Error::InvalidLength(6)])
+        );
+    }
+
+    #[test]
+    fn test_trailing_pad() {
+        let options = DecodeOptions {
+            trailing_pad: Some(b'.'),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options("VPRomVE...", &options).unwrap(),
+            b"aaaaa"
+        );
+        assert_eq!(
+            decode_with_options("VPRomVE..\n", &options).unwrap(),
+            b"aaaaa"
+        );
+        assert_eq!(decode_with_options("VPRomVE", &options).unwrap(), b"aaaaa");
+        assert_eq!(
+            decode_with_options("VPR.omVE", &options),
+            Err(Error::InvalidCharacter {
+                byte: b'.',
+                offset: 3
+            })
+        );
+        assert!(decode("VPRomVE.").is_err());
+    }
+
+    #[test]
+    fn test_trailing_pad_in_alphabet() {
+        // '=' is a digit in the RFC 1924 alphabet, so data ending in it can't be told apart from
+        // padding
+        assert_eq!(decode("VPRo=").unwrap().len(), 4);
+        let options = DecodeOptions {
+            trailing_pad: Some(b'='),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options("VPRo=", &options),
+            Err(Error::InvalidAlphabet(b'='))
+        );
+        assert_eq!(
+            decode_with_options("VPRomVE===\n", &options),
+            Err(Error::InvalidAlphabet(b'='))
+        );
+
+        let options = DecodeOptions {
+            trailing_pad: Some(b'\n'),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(
+            decode_with_options("VPRom", &options),
+            Err(Error::InvalidAlphabet(b'\n'))
+        );
+    }
+
+    #[test]
+    fn test_decode_whitespace_only() {
+        assert!(decode("   ").unwrap().is_empty());
+        assert!(decode("\n\n").unwrap().is_empty());
+        assert_eq!(decode("VP\nRom").unwrap(), b"aaaa");
+        assert_eq!(decode(" \tVP\r\nRom\n\n VE ").unwrap(), b"aaaaa");
+    }
+
+    #[test]
+    fn test_whitespace_positions() {
+        let encoded = "VPRomVPRn";
+        for i in 0..=encoded.len() {
+            for ws in [" ", "\n", "\r\n", "\t", "\x0b"] {
+                let spaced = format!("{}{ws}{}", &encoded[..i], &encoded[i..]);
+                assert_eq!(decode(&spaced).unwrap(), b"aaaaaaa", "decoding {spaced:?}");
+            }
+        }
+        assert_eq!(decode("VP Rom").unwrap(), decode("VPRom").unwrap());
+    }
+
+    #[test]
+    fn test_decode_remainders() {
+        // Encoded data with 0, 2, 3, or 4 characters after the last full group decodes to 0, 1,
+        // 2, or 3 trailing bytes, while a single leftover character means the length is invalid
+        let encoded = encode(b"abcdefgh");
+        for len in 0..=encoded.len() {
+            match len % 5 {
+                1 => assert!(matches!(
+                    decode(&encoded[..len]),
+                    Err(Error::InvalidLength(n)) if n == len
+                )),
+                extra => {
+                    let decoded = decode(&encoded[..len]).unwrap();
+                    let expected_len = len / 5 * 4 + extra.saturating_sub(1);
+                    assert_eq!(decoded, b"abcdefgh"[..expected_len]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_remainder_writes() {
+        // Encoded lengths of 5k + 2, 5k + 3 and 5k + 4 decode to exactly decoded_len() bytes,
+        // every one of which is written, and nothing past them is touched
+        for k in 0..3 {
+            for extra in 1..=3 {
+                let data = vec![0xff; k * 4 + extra];
+                let encoded = encode(&data);
+                assert_eq!(encoded.len(), k * 5 + extra + 1);
+                assert_eq!(decoded_len(encoded.len()), data.len());
+
+                let decoded = decode(&encoded).unwrap();
+                assert_eq!(decoded, data);
+                assert_eq!(decoded.capacity(), data.len());
+
+                let mut out = vec![0x55; data.len() + 4];
+                assert_eq!(decode_slice(&encoded, &mut out).unwrap(), data.len());
+                assert_eq!(out[..data.len()], data[..]);
+                assert!(out[data.len()..].iter().all(|&b| b == 0x55));
+            }
+        }
+    }
+
+    #[test]
+    fn test_tail_padding() {
+        // Every possible 1, 2 and 3 byte tail survives padding on both sides
+        fn round_trip(extra: &[u8]) {
+            let tail = encode_tail(&RFC1924, extra);
+            let mut group = GroupDecoder::default();
+            for (offset, &c) in tail[..extra.len() + 1].iter().enumerate() {
+                assert_eq!(
+                    group
+                        .push(RFC1924.char85_to_byte(c, offset).unwrap(), offset)
+                        .unwrap(),
+                    None
+                );
+            }
+            let (bytes, len) = group.finish().unwrap();
+            assert_eq!(bytes[..len], *extra);
+        }
+
+        // Rounding only ever threatens the last byte, so 3 byte tails cover every pair of final
+        // bytes behind a spread of first bytes rather than all 2^24 tails
+        for x in 0..=0xffff_u16 {
+            let [b, c] = x.to_be_bytes();
+            if b == 0 {
+                round_trip(&[c]);
+            }
+            round_trip(&[b, c]);
+            for a in [0x00, 0x01, 0x55, 0x7f, 0x80, 0xaa, 0xfe, 0xff] {
+                round_trip(&[a, b, c]);
+            }
+        }
+
+        // A padded group can hold no more than the highest digit allows, and padding with more
+        // made short groups like this one, which decode to 3 bytes, overflow
+        assert_eq!(decode("|NsB").unwrap(), [0xff; 3]);
+    }
+
+    #[test]
+    fn test_tail_round_trip() {
+        // Tails behind a full group go through encode() and decode() unchanged, with the
+        // extremes in every position
+        let values = [0x00, 0x01, 0x54, 0x55, 0x7f, 0x80, 0xfe, 0xff];
+        let mut data = vec![0xff; 4];
+        for &a in &values {
+            for tail in [vec![a], vec![a, 0xff], vec![0xff, a], vec![0x00, a]] {
+                data.truncate(4);
+                data.extend_from_slice(&tail);
+                assert_eq!(decode(encode(&data)).unwrap(), data);
+            }
+            for &b in &values {
+                for &c in &values {
+                    data.truncate(4);
+                    data.extend_from_slice(&[a, b, c]);
+                    let encoded = encode(&data);
+                    assert_eq!(encoded.len(), 9);
+                    assert_eq!(decode(&encoded).unwrap(), data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_wrapped() {
+        let data: Vec<u8> = (0..=255).collect();
+        let encoded = encode(&data);
+
+        let wrapped = encode_wrapped(&data, 60);
+        let lines: Vec<&str> = wrapped.split('\n').collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[..5].iter().all(|line| line.len() == 60));
+        assert_eq!(lines.concat(), encoded);
+        assert_eq!(decode(&wrapped).unwrap(), data);
+
+        // Exactly one line's worth of output has no line break at all
+        assert_eq!(encode_wrapped(b"aaaa", 5), "VPRom");
+        assert_eq!(encode_wrapped(b"aaaaa", 5), "VPRom\nVE");
+        assert_eq!(encode_wrapped(&data, 0), encoded);
+    }
+
+    #[test]
+    fn test_encode_line() {
+        assert_eq!(encode_line(b"aaaaa"), "VPRomVE\n");
+        assert_eq!(encode_wrapped_line(b"aaaaa", 5), "VPRom\nVE\n");
+        assert_eq!(encode_wrapped_line(b"aaaa", 5), "VPRom\n");
+
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(encode_line(&data)).unwrap(), data);
+        assert_eq!(decode(encode_wrapped_line(&data, 64)).unwrap(), data);
+    }
+
+    #[test]
+    fn test_ipv6() {
+        // The example from RFC 1924
+        let addr: Ipv6Addr = "1080:0:0:0:8:800:200C:417A".parse().unwrap();
+        assert_eq!(encode_ipv6(addr), "4)+k&C#VzJ4br>0wv%Yp");
+        assert_eq!(decode_ipv6("4)+k&C#VzJ4br>0wv%Yp").unwrap(), addr);
+
+        assert_eq!(encode_ipv6(Ipv6Addr::UNSPECIFIED), "00000000000000000000");
+        let max = Ipv6Addr::from(u128::MAX);
+        assert_eq!(decode_ipv6(&encode_ipv6(max)).unwrap(), max);
+
+        assert!(matches!(
+            decode_ipv6("4)+k&C#VzJ4br>0wv%Y"),
+            Err(Error::InvalidLength(19))
+        ));
+        assert!(matches!(
+            decode_ipv6("~~~~~~~~~~~~~~~~~~~~"),
+            Err(Error::Overflow { offset: 0 })
+        ));
+        assert!(matches!(
+            decode_ipv6("4)+k&C#VzJ4br>0wv%Y\""),
+            Err(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 19
+            })
+        ));
+    }
+
+    #[test]
+    fn test_u128() {
+        assert_eq!(encode_u128(1), "00000000000000000001");
+        assert_eq!(decode_u128("00000000000000000001").unwrap(), 1);
+        assert_eq!(encode_u128(u128::MAX), "=r54lj&NUUO~Hi%c2ym0");
+        assert_eq!(decode_u128("=r54lj&NUUO~Hi%c2ym0").unwrap(), u128::MAX);
+        assert_eq!(
+            decode_u128("=r54lj&NUUO~Hi%c2ym1"),
+            Err(Error::Overflow { offset: 0 })
+        );
+        assert_eq!(decode_u128("0"), Err(Error::InvalidLength(1)));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("VPRom VPRn").is_ok());
+        assert!(validate("").is_ok());
+        assert!(matches!(
+            validate("VPR\"m"),
+            Err(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 3
+            })
+        ));
+        assert!(matches!(
+            validate("~~~~~"),
+            Err(Error::Overflow { offset: 0 })
+        ));
+        assert!(matches!(validate("VPRom V"), Err(Error::InvalidLength(6))));
+    }
+
+    #[test]
+    fn test_encode_slice() {
+        let mut out = [0u8; 12];
+        assert_eq!(encode_slice(b"aaaaaaa", &mut out).unwrap(), 9);
+        assert_eq!(&out[..9], b"VPRomVPRn");
+
+        let mut out = [0u8; 8];
+        assert!(matches!(
+            encode_slice(b"aaaaaaa", &mut out),
+            Err(Error::BufferTooSmall)
+        ));
+        assert_eq!(out, [0; 8]);
+    }
+
+    #[test]
+    fn test_decode_slice() {
+        let mut out = [0u8; decoded_len(11)];
+        assert_eq!(decode_slice("VPRom\nVPRn", &mut out).unwrap(), 7);
+        assert_eq!(&out[..7], b"aaaaaaa");
+
+        let mut out = [0u8; 6];
+        assert!(matches!(
+            decode_slice("VPRomVPRn", &mut out),
+            Err(Error::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        assert_eq!(format!("{}", Base85Display(&data)), encode(&data));
+        assert_eq!(Base85Display(b"aaaaaaa").to_string(), "VPRomVPRn");
+        assert_eq!(Base85Display(b"").to_string(), "");
+    }
+
+    #[test]
+    fn test_checked() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+
+        for data in [&b""[..], b"a", b"aaaaaaa"] {
+            assert_eq!(decode_checked(encode_checked(data)).unwrap(), data);
+        }
+
+        // Flip a character in the middle of the payload
+        let mut encoded = encode_checked(b"aaaaaaaa").into_bytes();
+        encoded[7] = if encoded[7] == b'0' { b'1' } else { b'0' };
+        assert!(matches!(
+            decode_checked(&encoded),
+            Err(Error::ChecksumMismatch)
+        ));
+        assert!(matches!(
+            decode_checked("VPO"),
+            Err(Error::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_base85_encoder() {
+        let data: Vec<u8> = (0..=255).step_by(7).collect();
+        let encoded = encode(&data);
+
+        for split in 0..=data.len() {
+            let mut encoder = Base85Encoder::new();
+            let mut outdata = encoder.update(&data[..split]);
+            outdata += &encoder.update(&data[split..]);
+            outdata += &encoder.finalize();
+            assert_eq!(outdata, encoded);
+        }
+
+        // Feeding a byte at a time exercises the carry on every call
+        let mut encoder = Base85Encoder::new();
+        let mut outdata: String = data.chunks(1).map(|b| encoder.update(b)).collect();
+        outdata += &encoder.finalize();
+        assert_eq!(outdata, encoded);
+    }
+
+    #[test]
+    fn test_base85_decoder() {
+        let data: Vec<u8> = (0..=255).step_by(7).collect();
+        let encoded = encode_wrapped(&data, 16);
+
+        for split in 0..=encoded.len() {
+            let mut decoder = Base85Decoder::new();
+            let mut outdata = decoder.update(&encoded[..split]).unwrap();
+            outdata.extend(decoder.update(&encoded[split..]).unwrap());
+            outdata.extend(decoder.finalize().unwrap());
+            assert_eq!(outdata, data);
+        }
+
+        // Offsets count from the start of the first piece
+        let mut decoder = Base85Decoder::new();
+        decoder.update("VPRo").unwrap();
+        assert!(matches!(
+            decoder.update("m,"),
+            Err(Error::InvalidCharacter {
+                byte: b',',
+                offset: 5
+            })
+        ));
+
+        let mut decoder = Base85Decoder::new();
+        assert_eq!(decoder.update("VPRomV").unwrap(), b"aaaa");
+        decoder.update("\n").unwrap();
+        let err = decoder.finalize().unwrap_err();
+        assert!(matches!(
+            err,
+            Error::UnexpectedEof {
+                offset: 7,
+                in_group: true
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Unexpected end of input at offset 7 inside a group"
+        );
+    }
+
+    #[test]
+    fn test_scratch_decoder() {
+        let mut decoder = ScratchDecoder::with_capacity(8);
+        assert_eq!(decoder.decode("VPRomVPRom").unwrap(), b"aaaaaaaa");
+        assert_eq!(decoder.decode("VE").unwrap(), b"a");
+        assert_eq!(decoder.decode("").unwrap(), b"");
+        assert!(decoder.decode("VPRo\"m").is_err());
+        assert_eq!(decoder.decode("VPRomVE").unwrap(), b"aaaaa");
+        assert_eq!(decoder.buf.capacity(), 8);
+    }
+
+    #[test]
+    fn test_decode_limited() {
+        assert_eq!(decode_limited("VPRomVE", 5).unwrap(), b"aaaaa");
+        assert_eq!(decode_limited("VPRom\n\n\nVE", 5).unwrap(), b"aaaaa");
+        assert!(matches!(
+            decode_limited("VPRomVE", 4),
+            Err(Error::OutputTooLarge(4))
+        ));
+        assert!(matches!(
+            decode_limited("VPRomV", 4),
+            Err(Error::InvalidLength(6))
+        ));
+    }
+
+    #[test]
+    fn test_decode_prefix() {
+        assert_eq!(decode_prefix("VPRomVE").unwrap(), (b"aaaaa".to_vec(), 7));
+        assert_eq!(
+            decode_prefix("VPRom VE,rest").unwrap(),
+            (b"aaaaa".to_vec(), 8)
+        );
+        assert_eq!(decode_prefix("\"VPRom").unwrap(), (Vec::new(), 0));
+        assert!(matches!(
+            decode_prefix("VPRomV,"),
+            Err(Error::InvalidLength(6))
+        ));
+    }
+
+    #[test]
+    fn test_encode_fmt() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut out = String::from("prefix:");
+        encode_fmt(&data, &mut out).unwrap();
+        assert_eq!(out, "prefix:".to_string() + &encode(&data));
+    }
+
+    #[test]
+    fn test_decode_verbose() {
+        let (decoded, stats) = decode_verbose("VPRom\r\nVPRom\r\n VE").unwrap();
+        assert_eq!(decoded, b"aaaaaaaaa");
+        assert_eq!(
+            stats,
+            DecodeStats {
+                bytes_out: 9,
+                chars_consumed: 17,
+                whitespace_skipped: 5,
+            }
+        );
+        assert!(decode_verbose("VPRomV").is_err());
+    }
+
+    #[test]
+    fn test_base85_reader() {
+        let encoded = encode_wrapped(b"Hello, world!", 4);
+        let mut reader = Base85Reader::new(&encoded);
+        assert_eq!(reader.next_byte(), Some(Ok(b'H')));
+        assert_eq!(reader.next_byte(), Some(Ok(b'e')));
+        let rest: Result<Vec<u8>> = reader.collect();
+        assert_eq!(rest.unwrap(), b"llo, world!");
+
+        assert_eq!(Base85Reader::new("").next_byte(), None);
+
+        // The bytes of the groups before an error still come out
+        let mut reader = Base85Reader::new("VPRomVP\"n");
+        for _ in 0..4 {
+            assert_eq!(reader.next_byte(), Some(Ok(b'a')));
+        }
+        assert_eq!(
+            reader.next_byte(),
+            Some(Err(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 7
+            }))
+        );
+        assert_eq!(reader.next_byte(), None);
+
+        let mut reader = Base85Reader::new(b"VPRomV");
+        assert_eq!(reader.nth(4), Some(Err(Error::InvalidLength(6))));
+        assert_eq!(reader.next(), None);
+    }
+
+    #[test]
+    fn test_decode_chars() {
+        let encoded = encode_wrapped(b"Hello, world!", 8);
+        assert_eq!(
+            decode_chars(encoded.chars()).unwrap(),
+            decode(&encoded).unwrap()
+        );
+        assert_eq!(
+            decode_chars(encoded.chars().filter(|&c| c != '\n')).unwrap(),
+            b"Hello, world!"
+        );
+        assert!(matches!(
+            decode_chars("VPé".chars()),
+            Err(Error::InvalidCharacter {
+                byte: 0xc3,
+                offset: 2
+            })
+        ));
+        assert!(matches!(
+            decode_chars("VPRomV".chars()),
+            Err(Error::InvalidLength(6))
+        ));
+    }
+
+    #[test]
+    fn test_decode_fallible_iter() {
+        let ok = b"VPRom VE".iter().map(|&c| Ok::<u8, &str>(c));
+        assert_eq!(decode_fallible_iter(ok).unwrap(), b"aaaaa");
+
+        // The iterator fails partway through a group
+        let failing = b"VPRomV"
+            .iter()
+            .map(|&c| Ok(c))
+            .chain([Err("connection reset")])
+            .chain([Ok(b'E')]);
+        assert_eq!(
+            decode_fallible_iter(failing),
+            Err(DecodeOrIo::Io("connection reset"))
+        );
+
+        let bad = b"VP\"om".iter().map(|&c| Ok::<u8, &str>(c));
+        assert_eq!(
+            decode_fallible_iter(bad),
+            Err(DecodeOrIo::Decode(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 2
+            }))
+        );
+    }
+
+    #[test]
+    fn test_base85_string() {
+        let encoded = Base85String::from(&b"aaaaa"[..]);
+        assert_eq!(encoded.as_str(), "VPRomVE");
+        assert_eq!(encoded.to_string(), "VPRomVE");
+        assert_eq!(Vec::try_from(&encoded).unwrap(), b"aaaaa");
+        let decoded: Vec<u8> = encoded.try_into().unwrap();
+        assert_eq!(decoded, b"aaaaa");
+
+        let bad = Base85String::from_encoded("VPRo\"m".to_string());
+        assert!(bad.decode().is_err());
+        assert_eq!(bad.into_string(), "VPRo\"m");
+    }
+
+    #[test]
+    fn test_decoded_bytes() {
+        let decoded: DecodedBytes = "VPRomVE".parse().unwrap();
+        assert_eq!(decoded.as_ref(), b"aaaaa");
+        assert_eq!(Vec::from(decoded), b"aaaaa");
+        assert_eq!(
+            "VPRo\"m".parse::<DecodedBytes>(),
+            Err(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_encode_capacity() {
+        // The output is allocated once at exactly the right size rather than grown
+        let data: Vec<u8> = (0..=255).cycle().take(0x10003).collect();
+        for len in [0, 1, 4, 5, 1000, data.len()] {
+            let encoded = encode(&data[..len]);
+            assert_eq!(encoded.len(), encoded_len(len));
+            assert_eq!(encoded.capacity(), encoded_len(len));
+        }
+    }
+
+    #[test]
+    fn test_encode_array() {
+        const UUID: [u8; 16] = [
+            0x12, 0x3e, 0x45, 0x67, 0xe8, 0x9b, 0x12, 0xd3, 0xa4, 0x56, 0x42, 0x66, 0x14, 0x17,
+            0x40, 0x00,
+        ];
+        const ENCODED: [u8; 20] = encode_array_const(&UUID);
+        assert_eq!(encode_array(UUID), encode(UUID));
+        assert_eq!(ENCODED[..], *encode(UUID).as_bytes());
+
+        let tail: [u8; 9] = encode_array_const(b"aaaaaaa");
+        assert_eq!(&tail, b"VPRomVPRn");
+        let empty: [u8; 0] = encode_array_const(&[]);
+        assert!(empty.is_empty());
+
+        // Both directions can run at compile time, including turning the result into a &str
+        const DECODED: [u8; 16] = decode_array_const(&ENCODED);
+        const ENCODED_STR: &str = match core::str::from_utf8(&ENCODED) {
+            Ok(s) => s,
+            Err(_) => panic!("encoded data isn't ASCII"),
+        };
+        assert_eq!(DECODED, UUID);
+        assert_eq!(ENCODED_STR, encode(UUID));
+        let tail: [u8; 7] = decode_array_const(b"VPRomVPRn");
+        assert_eq!(&tail, b"aaaaaaa");
+        assert_eq!(decode_array_const::<5, 4>(b"|NsC0"), [0xff; 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid character")]
+    fn test_decode_array_const_invalid() {
+        let _: [u8; 4] = decode_array_const(b"VP\"om");
+    }
+
+    #[test]
+    fn test_str() {
+        let encoded = encode_str("héllo, wörld");
+        assert_eq!(encoded, encode("héllo, wörld".as_bytes()));
+        assert_eq!(decode_to_string(&encoded).unwrap(), "héllo, wörld");
+        assert!(matches!(
+            decode_to_string(encode(b"ab\xffcd")),
+            Err(Error::InvalidUtf8 { offset: 2, .. })
+        ));
+        assert!(decode_to_string("VPRomV").is_err());
+    }
+
+    #[test]
+    fn test_decode_cow() {
+        assert!(matches!(decode_cow(""), Ok(Cow::Borrowed(b)) if b.is_empty()));
+        assert_eq!(decode_cow("VPRomVE").unwrap(), &b"aaaaa"[..]);
+        assert_eq!(decode_cow(&b"VPRom"[..]).unwrap().into_owned(), b"aaaa");
+        assert!(decode_cow("VPRomV").is_err());
+    }
+
+    #[test]
+    fn test_endianness() {
+        let little = EncodeOptions {
+            endianness: Endianness::Little,
+            ..EncodeOptions::default()
+        };
+        let little_decode = DecodeOptions {
+            endianness: Endianness::Little,
+            ..DecodeOptions::default()
+        };
+
+        // A little-endian group is the big-endian encoding of the bytes reversed
+        assert_eq!(encode_with_options(b"abcd", &little), encode(b"dcba"));
+        assert_eq!(
+            encode_with_options(b"abcd", &EncodeOptions::default()),
+            encode(b"abcd")
+        );
+
+        let data: Vec<u8> = (0..=255).step_by(3).collect();
+        for len in 0..data.len() {
+            let encoded = encode_with_options(&data[..len], &little);
+            assert_eq!(encoded.len(), encoded_len(len));
+            assert_eq!(
+                decode_with_options(&encoded, &little_decode).unwrap(),
+                data[..len]
+            );
+        }
+    }
+
+    #[test]
+    fn test_group() {
+        assert_eq!(&encode_group(b"aaaa"), b"VPRom");
+        assert_eq!(&decode_group(b"VPRom").unwrap(), b"aaaa");
+        assert_eq!(encode_group(&[0xff; 4]), *b"|NsC0");
+        assert_eq!(decode_group(b"|NsC0").unwrap(), [0xff; 4]);
+        assert_eq!(decode_group(b"|NsC1"), Err(Error::Overflow { offset: 0 }));
+        assert_eq!(
+            decode_group(b"VP\"om"),
+            Err(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_group_value() {
+        assert_eq!(MAX_GROUP_VALUE, 4437053124);
+        assert_eq!(POW85_4 as u64 * 85 - 1, MAX_GROUP_VALUE);
+        assert!(group_fits_u32(u32::MAX as u64));
+        assert!(!group_fits_u32(u32::MAX as u64 + 1));
+        assert!(!group_fits_u32(MAX_GROUP_VALUE));
+        assert_eq!(group_digits(u32::MAX), [82, 23, 54, 12, 0]);
+    }
+
+    #[test]
+    fn test_group_digits() {
+        // Checking all 2^32 values takes too long for every test run, so check a sample plus the
+        // values either side of every change of the first two digits. test_group_digits_all()
+        // checks the rest.
+        for decnum in (0..=u32::MAX).step_by(9973) {
+            assert_eq!(group_digits(decnum), divided_digits(decnum), "{decnum}");
+        }
+        for i in 0..=85 * 85 {
+            let edge = (i as u64 * 85 * 85 * 85).min(u32::MAX as u64) as u32;
+            for decnum in [edge.saturating_sub(1), edge, edge.saturating_add(1)] {
+                assert_eq!(group_digits(decnum), divided_digits(decnum), "{decnum}");
+            }
+        }
+        assert_eq!(group_digits(u32::MAX), divided_digits(u32::MAX));
+    }
+
+    #[test]
+    #[ignore = "checks all 2^32 values; run with --release --ignored"]
+    fn test_group_digits_all() {
+        for decnum in 0..=u32::MAX {
+            assert_eq!(group_digits(decnum), divided_digits(decnum), "{decnum}");
+        }
+    }
+
+    // The straightforward division chain the digits from group_digits() have to match
+    fn divided_digits(decnum: u32) -> [u8; 5] {
+        let mut digits = [0u8; 5];
+        let mut remainder = decnum;
+        for (i, digit) in digits.iter_mut().enumerate() {
+            let place = 85u32.pow(4 - i as u32);
+            *digit = (remainder / place) as u8;
+            remainder %= place;
+        }
+        digits
+    }
+
+    #[test]
+    fn test_encode_many() {
+        let inputs: [&[u8]; 3] = [b"aaaaa", b"aaa", b""];
+        assert_eq!(encode_many(&inputs), ["VPRomVE", "VPRn", ""]);
+
+        // Joining the separate encodings differs from encoding the joined data
+        let concat = encode_concat(&inputs);
+        assert_eq!(concat, encode(b"aaaaaaaa"));
+        assert_eq!(concat, "VPRomVPRom");
+        assert_ne!(concat, encode_many(&inputs).concat());
+        assert_eq!(encode_concat(&[]), "");
+    }
+
+    #[test]
+    fn test_verify_decodable() {
+        assert_eq!(verify_decodable("").unwrap(), 0);
+        assert_eq!(verify_decodable("VPRom\nVPRn").unwrap(), 7);
+        assert_eq!(
+            verify_decodable("|NsC1"),
+            Err(Error::Overflow { offset: 0 })
+        );
+        assert_eq!(
+            verify_decodable("VPRo\"m"),
+            Err(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_reformat() {
+        let data: Vec<u8> = (0..=255).collect();
+        let messy: String = encode_wrapped(&data, 7)
+            .split('\n')
+            .collect::<Vec<_>>()
+            .join(" \r\n\t");
+        assert_eq!(reformat(&messy, 60).unwrap(), encode_wrapped(&data, 60));
+        assert_eq!(reformat(&messy, 0).unwrap(), encode(&data));
+        assert_eq!(reformat(" VPRom\nVE ", 5).unwrap(), "VPRom\nVE");
+        assert_eq!(reformat("", 5).unwrap(), "");
+
+        assert_eq!(
+            reformat("VPRom\n\"E", 5),
+            Err(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 6
+            })
+        );
+        assert_eq!(
+            reformat("VPRom\n|NsC1", 5),
+            Err(Error::Overflow { offset: 6 })
+        );
+        assert_eq!(reformat("VPRom V", 5), Err(Error::InvalidLength(6)));
+    }
+
+    #[test]
+    fn test_decode_with_comments() {
+        let annotated = "/ key for the staging server\nVPRom / first group\r\nVE/";
+        assert_eq!(decode_with_comments(annotated, '/').unwrap(), b"aaaaa");
+        assert_eq!(decode_with_comments("VPRomVE", '/').unwrap(), b"aaaaa");
+        assert!(matches!(
+            decode_with_comments("VP,Rom", '/'),
+            Err(Error::InvalidCharacter {
+                byte: b',',
+                offset: 2
+            })
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_comment_prefix_in_alphabet() {
+        let _ = decode_with_comments("VPRom", '#');
+    }
+
+    #[test]
+    fn test_decode_debug() {
+        assert_eq!(decode_debug("VPRomVE").unwrap(), b"aaaaa");
+        assert_eq!(
+            decode_debug("VPRomVPRomVP\nRo\"mVPRom")
+                .unwrap_err()
+                .to_string(),
+            "Unexpected character '34' at offset 15\nRomVP.Ro\"mVPRom\n        ^"
+        );
+        assert_eq!(
+            decode_debug("|NsC1").unwrap_err().to_string(),
+            "Group starting at offset 0 is too large to fit in 4 bytes\n|NsC1\n^"
+        );
+        assert_eq!(decode_debug("VPRomV"), Err(Error::InvalidLength(6)));
+    }
+
+    #[test]
+    fn test_framed() {
+        let large = vec![0x5a; 100000];
+        let messages: [&[u8]; 4] = [b"", b"hello", &large, b""];
+        let encoded = encode_framed(&messages);
+        assert_eq!(decode_framed(&encoded).unwrap(), messages);
+
+        assert_eq!(encode_framed(&[]), "");
+        assert!(decode_framed("").unwrap().is_empty());
+        assert_eq!(
+            decode_framed(encode([0, 0, 0, 1, b'a', 0, 0, 0, 2, b'b'])),
+            Err(Error::InvalidFrame { offset: 5 })
+        );
+        assert_eq!(
+            decode_framed(encode([0, 0])),
+            Err(Error::InvalidFrame { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn test_encode_in_place() {
+        let data: Vec<u8> = (0..=255).rev().collect();
+        for len in [0, 1, 2, 3, 4, 5, 7, 8, 255, 256] {
+            let mut buf = data[..len].to_vec();
+            encode_in_place(&mut buf);
+            assert_eq!(buf, encode(&data[..len]).as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_decode_in_place() {
+        let data: Vec<u8> = (0..=255).collect();
+        for len in [0, 1, 4, 5, 255, 256] {
+            let mut buf = encode_wrapped(&data[..len], 7).into_bytes();
+            assert_eq!(decode_in_place(&mut buf).unwrap(), len);
+            assert_eq!(buf, data[..len]);
+        }
+
+        let mut buf = b"VPRomV".to_vec();
+        assert_eq!(decode_in_place(&mut buf), Err(Error::InvalidLength(6)));
+    }
+
+    #[test]
+    fn test_error_source() {
+        use core::error::Error as _;
+
+        let err = decode_to_string(encode(b"ab\xffcd")).unwrap_err();
+        assert!(err.source().unwrap().is::<core::str::Utf8Error>());
+        let err = decode_debug("VPRo\"m").unwrap_err();
+        assert_eq!(
+            err.source().unwrap().downcast_ref::<Error>(),
+            Some(&Error::InvalidCharacter {
+                byte: b'"',
+                offset: 4
+            })
+        );
+        assert!(Error::InvalidLength(1).source().is_none());
+    }
+
+    #[test]
+    fn test_exact() {
+        for len in 0..10usize {
+            let data: Vec<u8> = (0..len).map(|i| 0xff - i as u8).collect();
+            let encoded = encode_exact(&data);
+            assert_eq!(encoded.len(), len.div_ceil(4) * 5 + 1);
+            assert_eq!(decode_exact(&encoded).unwrap(), data);
+        }
+        assert_eq!(encode_exact(b"aaaaa"), "VPRomVE_OC1");
+        assert_eq!(encode_exact(b""), "0");
+
+        assert!(matches!(decode_exact(""), Err(Error::InvalidLength(0))));
+        assert!(matches!(
+            decode_exact("VPRomVE"),
+            Err(Error::InvalidCharacter {
+                byte: b'E',
+                offset: 6
+            })
+        ));
+        assert!(matches!(
+            decode_exact("2"),
+            Err(Error::InvalidCharacter {
+                byte: b'2',
+                offset: 0
+            })
+        ));
+        assert!(matches!(
+            decode_exact("VPRomVE1"),
+            Err(Error::InvalidLength(7))
+        ));
+    }
+
+    #[test]
+    fn test_encode_to_bytes() {
+        let data: Vec<u8> = (0..=255).collect();
+        for len in [0, 1, 4, 255, 256] {
+            assert_eq!(
+                encode_to_bytes(&data[..len]),
+                encode(&data[..len]).into_bytes()
             );
         }
     }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(encode_blocks(b"aaaaaaaa").unwrap(), "VPRomVPRom");
+        assert_eq!(encode_blocks(b"").unwrap(), "");
+        assert_eq!(encode_blocks(b"aaaaa"), Err(Error::InvalidLength(5)));
+
+        assert_eq!(decode_blocks("VPRomVPRom").unwrap(), b"aaaaaaaa");
+        assert_eq!(decode_blocks("VPRomVE"), Err(Error::InvalidLength(7)));
+        assert_eq!(
+            decode_blocks("VPRo\nmVPRo"),
+            Err(Error::InvalidCharacter {
+                byte: b'\n',
+                offset: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        let data: Vec<u8> = (0..=255).cycle().take(PROGRESS_CHUNK_LEN * 2 + 5).collect();
+
+        let mut calls = Vec::new();
+        let encoded = encode_with_progress(&data, |done, total| calls.push((done, total)));
+        assert_eq!(encoded, encode(&data));
+        let total = data.len();
+        assert_eq!(
+            calls,
+            [
+                (PROGRESS_CHUNK_LEN, total),
+                (PROGRESS_CHUNK_LEN * 2, total),
+                (total, total)
+            ]
+        );
+
+        let mut calls = Vec::new();
+        let decoded = decode_with_progress(&encoded, |done, total| calls.push((done, total)));
+        assert_eq!(decoded.unwrap(), data);
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[2], (encoded.len(), encoded.len()));
+
+        let mut calls = 0;
+        assert_eq!(encode_with_progress(b"", |_, _| calls += 1), "");
+        assert!(decode_with_progress("", |_, _| calls += 1)
+            .unwrap()
+            .is_empty());
+        assert_eq!(calls, 2);
+        assert_eq!(
+            decode_with_progress("VPRomV", |_, _| {}),
+            Err(Error::InvalidLength(6))
+        );
+    }
+
+    #[test]
+    fn test_char_table() {
+        for c in 0..=255u8 {
+            match ALPHABET.iter().position(|&x| x == c) {
+                Some(value) => assert_eq!(RFC1924.char85_to_byte(c, 0).unwrap(), value as u8),
+                None => assert!(RFC1924.char85_to_byte(c, 0).is_err()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_alphabet_const() {
+        assert_eq!(ALPHABET.len(), 85);
+        for (i, c) in ALPHABET.iter().enumerate() {
+            assert!(!ALPHABET[i + 1..].contains(c));
+        }
+        assert_eq!(RFC1924.chars(), &ALPHABET);
+    }
+
+    #[test]
+    fn test_is_base85_char() {
+        for c in 0..=255 {
+            assert_eq!(is_base85_char(c), ALPHABET.contains(&c));
+        }
+        assert!(is_base85_whitespace(b'\t'));
+        assert!(is_base85_whitespace(0x0b));
+        assert!(!is_base85_whitespace(0x0c));
+        assert!(!is_base85_whitespace(b'0'));
+    }
+
+    #[test]
+    fn test_alphabet() {
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(encode_with(&RFC1924, &data), encode(&data));
+
+        // Reversing the alphabet reverses the value of every digit
+        let mut chars = *RFC1924.chars();
+        chars.reverse();
+        let reversed = Alphabet::new(&chars).unwrap();
+        assert_eq!(encode_with(&reversed, [0; 4]), "~~~~~");
+        assert_eq!(
+            decode_with(&reversed, encode_with(&reversed, &data)).unwrap(),
+            data
+        );
+        assert!(matches!(
+            decode_with(&reversed, "00000"),
+            Err(Error::Overflow { offset: 0 })
+        ));
+
+        chars[84] = chars[0];
+        assert!(matches!(
+            Alphabet::new(&chars),
+            Err(Error::InvalidAlphabet(b'~'))
+        ));
+        chars[84] = b' ';
+        assert!(matches!(
+            Alphabet::new(&chars),
+            Err(Error::InvalidAlphabet(b' '))
+        ));
+        chars[84] = 0x80;
+        assert!(matches!(
+            Alphabet::new(&chars),
+            Err(Error::InvalidAlphabet(0x80))
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(data in proptest::collection::vec(any::<u8>(), 0..=4096)) {
+            let encoded = encode(&data);
+            prop_assert_eq!(encoded.len(), encoded_len(data.len()));
+
+            let decoded = decode(&encoded).unwrap();
+            prop_assert!(decoded.capacity() <= encoded_len(data.len()));
+            prop_assert_eq!(decoded, data);
+        }
+
+        #[test]
+        fn prop_ordered(
+            pairs in proptest::collection::vec(any::<(u8, u8)>(), 0..32),
+            a4 in proptest::collection::vec(any::<[u8; 4]>(), 0..8),
+            b4 in proptest::collection::vec(any::<[u8; 4]>(), 0..8),
+        ) {
+            // Inputs of the same length
+            let a: Vec<u8> = pairs.iter().map(|p| p.0).collect();
+            let b: Vec<u8> = pairs.iter().map(|p| p.1).collect();
+            prop_assert_eq!(encode_with(&ORDERED, &a).cmp(&encode_with(&ORDERED, &b)), a.cmp(&b));
+
+            // Inputs with whole groups only
+            let (a4, b4) = (a4.concat(), b4.concat());
+            prop_assert_eq!(encode_with(&ORDERED, &a4).cmp(&encode_with(&ORDERED, &b4)), a4.cmp(&b4));
+            prop_assert_eq!(decode_with(&ORDERED, encode_with(&ORDERED, &a4)).unwrap(), a4);
+        }
+
+        #[test]
+        fn prop_decode_never_panics(data in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let _ = decode(&data);
+        }
+    }
 }
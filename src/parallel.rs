@@ -0,0 +1,43 @@
+//! Encoding large inputs on several threads at once with rayon
+
+use alloc::{string::String, vec};
+
+use rayon::prelude::*;
+
+use crate::{encode_slice, encoded_len};
+
+// The number of bytes each task encodes. This has to be a multiple of 4 so that only the last
+// chunk can have a partial group, and large enough that tasks aren't dominated by overhead.
+const PARALLEL_CHUNK_LEN: usize = 0x10000;
+
+/// encode_parallel() is like `encode()` but splits large inputs between rayon's threads. Every
+/// group of 4 bytes encodes on its own, so the output is identical to `encode()`.
+pub fn encode_parallel(indata: &[u8]) -> String {
+    let mut outdata = vec![0; encoded_len(indata.len())];
+    indata
+        .par_chunks(PARALLEL_CHUNK_LEN)
+        .zip(outdata.par_chunks_mut(encoded_len(PARALLEL_CHUNK_LEN)))
+        .for_each(|(chunk, outchunk)| {
+            // Each output chunk is exactly encoded_len(chunk.len()) long, and encode_slice()
+            // uses the vector code with the `simd` feature, like encode()
+            encode_slice(chunk, outchunk).expect("output chunk sized by encoded_len");
+        });
+
+    // SAFETY: the buffer starts out as zeroes and encode_slice() only writes ASCII characters
+    unsafe { String::from_utf8_unchecked(outdata) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_encode_parallel() {
+        let data: Vec<u8> = (0..=255).cycle().take(PARALLEL_CHUNK_LEN * 3 + 7).collect();
+        for len in [0, 3, PARALLEL_CHUNK_LEN, PARALLEL_CHUNK_LEN + 1, data.len()] {
+            assert_eq!(encode_parallel(&data[..len]), encode(&data[..len]));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Serde helpers for storing byte fields as Base85 strings, available with the `serde` feature.
+//!
+//! Use them on a `Vec<u8>` field with `#[serde(with = "base85::serde_bytes")]`. This keeps the
+//! field compact in text formats like JSON, where a plain byte array would become a list of
+//! numbers.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::Serializer;
+
+/// serialize() writes the bytes as a Base85-encoded string
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> core::result::Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]> + ?Sized,
+    S: Serializer,
+{
+    serializer.serialize_str(&crate::encode(bytes.as_ref()))
+}
+
+/// deserialize() reads a Base85-encoded string and decodes it
+pub fn deserialize<'de, D>(deserializer: D) -> core::result::Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(Base85Visitor)
+}
+
+struct Base85Visitor;
+
+impl Visitor<'_> for Base85Visitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a Base85-encoded string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> core::result::Result<Vec<u8>, E> {
+        crate::decode(v).map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Record {
+        name: String,
+        #[serde(with = "crate::serde_bytes")]
+        data: Vec<u8>,
+    }
+
+    #[test]
+    fn test_serde_bytes() {
+        let record = Record {
+            name: String::from("a"),
+            data: b"aaaaaaa".to_vec(),
+        };
+
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(json, r#"{"name":"a","data":"VPRomVPRn"}"#);
+        assert_eq!(serde_json::from_str::<Record>(&json).unwrap(), record);
+
+        let err = serde_json::from_str::<Record>(r#"{"name":"a","data":"VPRo\""}"#).unwrap_err();
+        assert!(err.to_string().starts_with("Unexpected character"));
+    }
+}
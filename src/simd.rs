@@ -0,0 +1,346 @@
+//! Group encoding and decoding for x86 CPUs, available with the `simd` feature. The base 85
+//! arithmetic, the byte swapping and the overflow checks are done a vector of groups at a time,
+//! but characters are still looked up in the alphabet one at a time, since an alphabet can be any
+//! 85 characters. The CPU is checked at runtime and anything the vector code doesn't handle,
+//! including the trailing partial group, is left to the scalar code.
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use crate::Alphabet;
+
+// For any u32 x, x / 85 == (x * DIV85_MAGIC) >> 38. The vector code takes the high half of a
+// 32x32 bit multiply and then shifts right by the remaining 6 bits.
+const DIV85_MAGIC: i32 = 3233857729_u32 as i32;
+const DIV85_SHIFT: i32 = 38;
+
+// encode_groups() encodes as many of the leading full groups as fill whole vectors into the start
+// of `out`, which needs room for 5 characters for every full group, and returns the number of
+// input bytes used
+pub(crate) fn encode_groups(alphabet: &Alphabet, indata: &[u8], out: &mut [u8]) -> usize {
+    assert!(out.len() >= indata.len() / 4 * 5);
+    if is_x86_feature_detected!("avx2") {
+        unsafe { encode_avx2(alphabet, indata, out) }
+    } else if is_x86_feature_detected!("sse2") {
+        unsafe { encode_sse2(alphabet, indata, out) }
+    } else {
+        0
+    }
+}
+
+// Encodes 8 groups at a time
+#[target_feature(enable = "avx2")]
+unsafe fn encode_avx2(alphabet: &Alphabet, indata: &[u8], out: &mut [u8]) -> usize {
+    // Groups are read big-endian, so the bytes of each lane need reversing
+    let bswap = _mm256_setr_epi8(
+        3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8, 15, 14, 13, 12, 3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8,
+        15, 14, 13, 12,
+    );
+    let magic = _mm256_set1_epi32(DIV85_MAGIC);
+    let base = _mm256_set1_epi32(85);
+
+    let mut digits = [[0u32; 8]; 5];
+
+    let blocks = indata.chunks_exact(32);
+    let consumed = indata.len() - blocks.remainder().len();
+    for (block, chars) in blocks.zip(out.chunks_exact_mut(40)) {
+        let mut x = _mm256_loadu_si256(block.as_ptr() as *const __m256i);
+        x = _mm256_shuffle_epi8(x, bswap);
+
+        // Peel off the digits from least significant to most
+        for digit in digits[1..].iter_mut().rev() {
+            let even = _mm256_srli_epi64(_mm256_mul_epu32(x, magic), DIV85_SHIFT);
+            let odd = _mm256_srli_epi64(
+                _mm256_mul_epu32(_mm256_srli_epi64(x, 32), magic),
+                DIV85_SHIFT,
+            );
+            let quotient = _mm256_or_si256(even, _mm256_slli_epi64(odd, 32));
+            let remainder = _mm256_sub_epi32(x, _mm256_mullo_epi32(quotient, base));
+            _mm256_storeu_si256(digit.as_mut_ptr() as *mut __m256i, remainder);
+            x = quotient;
+        }
+        _mm256_storeu_si256(digits[0].as_mut_ptr() as *mut __m256i, x);
+
+        for (group, c) in chars.chunks_exact_mut(5).enumerate() {
+            for (k, c) in c.iter_mut().enumerate() {
+                *c = alphabet.byte_to_char85(digits[k][group] as u8);
+            }
+        }
+    }
+
+    consumed
+}
+
+// Encodes 4 groups at a time using only SSE2, which lacks both byte shuffles and 32-bit
+// multiplies, so those are done with shifts instead
+#[target_feature(enable = "sse2")]
+unsafe fn encode_sse2(alphabet: &Alphabet, indata: &[u8], out: &mut [u8]) -> usize {
+    let magic = _mm_set1_epi32(DIV85_MAGIC);
+    let byte_mask = _mm_set1_epi32(0xFF00);
+
+    let mut digits = [[0u32; 4]; 5];
+
+    let blocks = indata.chunks_exact(16);
+    let consumed = indata.len() - blocks.remainder().len();
+    for (block, chars) in blocks.zip(out.chunks_exact_mut(20)) {
+        let v = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+        let mut x = _mm_or_si128(
+            _mm_or_si128(_mm_slli_epi32(v, 24), _mm_srli_epi32(v, 24)),
+            _mm_or_si128(
+                _mm_and_si128(_mm_slli_epi32(v, 8), _mm_slli_epi32(byte_mask, 8)),
+                _mm_and_si128(_mm_srli_epi32(v, 8), byte_mask),
+            ),
+        );
+
+        for digit in digits[1..].iter_mut().rev() {
+            let even = _mm_srli_epi64(_mm_mul_epu32(x, magic), DIV85_SHIFT);
+            let odd = _mm_srli_epi64(_mm_mul_epu32(_mm_srli_epi64(x, 32), magic), DIV85_SHIFT);
+            let quotient = _mm_or_si128(even, _mm_slli_epi64(odd, 32));
+            // quotient * 85 == quotient * 64 + quotient * 16 + quotient * 4 + quotient
+            let product = _mm_add_epi32(
+                _mm_add_epi32(_mm_slli_epi32(quotient, 6), _mm_slli_epi32(quotient, 4)),
+                _mm_add_epi32(_mm_slli_epi32(quotient, 2), quotient),
+            );
+            _mm_storeu_si128(
+                digit.as_mut_ptr() as *mut __m128i,
+                _mm_sub_epi32(x, product),
+            );
+            x = quotient;
+        }
+        _mm_storeu_si128(digits[0].as_mut_ptr() as *mut __m128i, x);
+
+        for (group, c) in chars.chunks_exact_mut(5).enumerate() {
+            for (k, c) in c.iter_mut().enumerate() {
+                *c = alphabet.byte_to_char85(digits[k][group] as u8);
+            }
+        }
+    }
+
+    consumed
+}
+
+// The most characters decode_block() decodes at once, 8 groups with AVX2, and the bytes they
+// decode to
+pub(crate) const DECODE_BLOCK_CHARS: usize = 40;
+pub(crate) const DECODE_BLOCK_BYTES: usize = 32;
+
+// The largest value the first 4 characters of a group can have without the whole group
+// overflowing 32 bits. 0xFFFFFFFF is a multiple of 85, so it's only reached with a last digit
+// of 0.
+const MAX_GROUP_PREFIX: i32 = (u32::MAX / 85) as i32;
+
+// decode_block() decodes the leading groups of `indata` a vector at a time into `out` and returns
+// the number of characters used, which is a multiple of 5 and may be 0. Whitespace, invalid
+// characters and groups that overflow all make it give up on the whole vector, leaving them to
+// the scalar code to skip or report.
+pub(crate) fn decode_block(
+    alphabet: &Alphabet,
+    indata: &[u8],
+    out: &mut [u8; DECODE_BLOCK_BYTES],
+) -> usize {
+    if indata.len() >= DECODE_BLOCK_CHARS && is_x86_feature_detected!("avx2") {
+        unsafe { decode_avx2(alphabet, &indata[..DECODE_BLOCK_CHARS], out) }
+    } else if indata.len() >= 20 && is_x86_feature_detected!("sse2") {
+        unsafe { decode_sse2(alphabet, &indata[..20], out) }
+    } else {
+        0
+    }
+}
+
+// Looks up the digits of `N` groups, laid out so that each digit position fills a vector. This is
+// a scalar loop over the alphabet's table. Returns None if any character isn't in the alphabet.
+#[inline(always)]
+fn lookup_digits<const N: usize>(alphabet: &Alphabet, indata: &[u8]) -> Option<[[i32; N]; 5]> {
+    let mut digits = [[0i32; N]; 5];
+    // Invalid characters look up as -1, so the sign bit collects them without a branch per
+    // character
+    let mut invalid = 0i8;
+    for (group, chars) in indata.chunks_exact(5).enumerate() {
+        for (k, &c) in chars.iter().enumerate() {
+            let value = alphabet.values[c as usize];
+            invalid |= value;
+            digits[k][group] = value as i32;
+        }
+    }
+    (invalid >= 0).then_some(digits)
+}
+
+// Decodes 8 groups at a time
+#[target_feature(enable = "avx2")]
+unsafe fn decode_avx2(alphabet: &Alphabet, indata: &[u8], out: &mut [u8; 32]) -> usize {
+    let Some(digits) = lookup_digits::<8>(alphabet, indata) else {
+        return 0;
+    };
+    let load = |k: usize| _mm256_loadu_si256(digits[k].as_ptr() as *const __m256i);
+    let base = _mm256_set1_epi32(85);
+
+    let mut x = load(0);
+    for k in 1..4 {
+        x = _mm256_add_epi32(_mm256_mullo_epi32(x, base), load(k));
+    }
+    let last = load(4);
+    let max = _mm256_set1_epi32(MAX_GROUP_PREFIX);
+    let overflow = _mm256_or_si256(
+        _mm256_cmpgt_epi32(x, max),
+        _mm256_and_si256(
+            _mm256_cmpeq_epi32(x, max),
+            _mm256_cmpgt_epi32(last, _mm256_setzero_si256()),
+        ),
+    );
+    if _mm256_movemask_epi8(overflow) != 0 {
+        return 0;
+    }
+    x = _mm256_add_epi32(_mm256_mullo_epi32(x, base), last);
+
+    // Groups are written big-endian, so the bytes of each lane need reversing
+    let bswap = _mm256_setr_epi8(
+        3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8, 15, 14, 13, 12, 3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8,
+        15, 14, 13, 12,
+    );
+    x = _mm256_shuffle_epi8(x, bswap);
+    _mm256_storeu_si256(out.as_mut_ptr() as *mut __m256i, x);
+    40
+}
+
+// Decodes 4 groups at a time using only SSE2, doing the multiplies with shifts as in
+// encode_sse2()
+#[target_feature(enable = "sse2")]
+unsafe fn decode_sse2(alphabet: &Alphabet, indata: &[u8], out: &mut [u8; 32]) -> usize {
+    let Some(digits) = lookup_digits::<4>(alphabet, indata) else {
+        return 0;
+    };
+    let load = |k: usize| _mm_loadu_si128(digits[k].as_ptr() as *const __m128i);
+    // x * 85 == x * 64 + x * 16 + x * 4 + x
+    let mul85 = |x| {
+        _mm_add_epi32(
+            _mm_add_epi32(_mm_slli_epi32(x, 6), _mm_slli_epi32(x, 4)),
+            _mm_add_epi32(_mm_slli_epi32(x, 2), x),
+        )
+    };
+
+    let mut x = load(0);
+    for k in 1..4 {
+        x = _mm_add_epi32(mul85(x), load(k));
+    }
+    let last = load(4);
+    let max = _mm_set1_epi32(MAX_GROUP_PREFIX);
+    let overflow = _mm_or_si128(
+        _mm_cmpgt_epi32(x, max),
+        _mm_and_si128(
+            _mm_cmpeq_epi32(x, max),
+            _mm_cmpgt_epi32(last, _mm_setzero_si128()),
+        ),
+    );
+    if _mm_movemask_epi8(overflow) != 0 {
+        return 0;
+    }
+    x = _mm_add_epi32(mul85(x), last);
+
+    let mut values = [0u32; 4];
+    _mm_storeu_si128(values.as_mut_ptr() as *mut __m128i, x);
+    for (bytes, value) in out.chunks_exact_mut(4).zip(values) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    20
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decode_chars, decode_with_options, encode_scalar, DecodeOptions, Endianness, RFC1924,
+    };
+    use rand::RngCore;
+
+    fn encode_scalar_only(indata: &[u8]) -> String {
+        let mut outdata = String::new();
+        encode_scalar(&RFC1924, indata, &mut outdata);
+        outdata
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let mut data = vec![0u8; 4099];
+        rand::thread_rng().fill_bytes(&mut data);
+        // Make sure the extremes are covered too
+        data[..32].fill(0xFF);
+        data[32..64].fill(0);
+
+        for len in [0, 3, 16, 31, 32, 33, 100, 4099] {
+            assert_eq!(
+                crate::encode(&data[..len]),
+                encode_scalar_only(&data[..len])
+            );
+            let mut out = vec![0u8; crate::encoded_len(len)];
+            crate::encode_slice(&data[..len], &mut out).unwrap();
+            assert_eq!(out, encode_scalar_only(&data[..len]).as_bytes());
+        }
+
+        if is_x86_feature_detected!("avx2") {
+            let mut outdata = [0u8; 5120];
+            let consumed = unsafe { encode_avx2(&RFC1924, &data, &mut outdata) };
+            assert_eq!(consumed, 4096);
+            assert_eq!(outdata, encode_scalar_only(&data[..consumed]).as_bytes());
+        }
+
+        let mut outdata = [0u8; 5120];
+        let consumed = unsafe { encode_sse2(&RFC1924, &data, &mut outdata) };
+        assert_eq!(consumed, 4096);
+        assert_eq!(outdata, encode_scalar_only(&data[..consumed]).as_bytes());
+    }
+
+    #[test]
+    fn test_simd_decode_matches_scalar() {
+        let mut data = vec![0u8; 4099];
+        rand::thread_rng().fill_bytes(&mut data);
+        data[..32].fill(0xFF);
+        let encoded = crate::encode(&data);
+        let decode_scalar_only = |s: &str| decode_chars(s.chars());
+
+        assert_eq!(crate::decode(&encoded).unwrap(), data);
+        for len in [0, 7, 20, 39, 40, 41, 100] {
+            assert_eq!(
+                crate::decode(&encoded[..len]),
+                decode_scalar_only(&encoded[..len])
+            );
+        }
+
+        // Whitespace, a bad character and an overflowing group in the middle of long input,
+        // which the vector code has to leave to the scalar code
+        let mut spaced = encoded.clone();
+        spaced.insert(93, '\n');
+        spaced.insert_str(211, " \t");
+        assert_eq!(crate::decode(&spaced).unwrap(), data);
+        for bad in ["\"", "|NsC1"] {
+            let mut broken = encoded.clone();
+            broken.replace_range(200..200 + bad.len(), bad);
+            assert_eq!(crate::decode(&broken), decode_scalar_only(&broken));
+        }
+        let mut max = encoded.clone();
+        max.replace_range(200..205, "|NsC0");
+        assert_eq!(crate::decode(&max), decode_scalar_only(&max));
+
+        let little = DecodeOptions {
+            endianness: Endianness::Little,
+            ..DecodeOptions::default()
+        };
+        let mut swapped = crate::decode(&encoded[..4095]).unwrap();
+        swapped.chunks_exact_mut(4).for_each(<[u8]>::reverse);
+        assert_eq!(
+            decode_with_options(&encoded[..4095], &little).unwrap(),
+            swapped
+        );
+
+        let mut block = [0u8; DECODE_BLOCK_BYTES];
+        if is_x86_feature_detected!("avx2") {
+            let used = unsafe { decode_avx2(&RFC1924, &encoded.as_bytes()[..40], &mut block) };
+            assert_eq!(used, 40);
+            assert_eq!(block, data[..32]);
+        }
+        let used = unsafe { decode_sse2(&RFC1924, &encoded.as_bytes()[..20], &mut block) };
+        assert_eq!(used, 20);
+        assert_eq!(block[..16], data[..16]);
+    }
+}
@@ -0,0 +1,49 @@
+//! Decoding short data without allocating
+
+use smallvec::SmallVec;
+
+use crate::{decode_groups, DecodeOptions, Result};
+
+/// decode_small() decodes data into a SmallVec which holds up to 32 bytes, enough for most keys
+/// and IDs, without allocating. Longer output moves to the heap as a Vec would.
+pub fn decode_small<T: AsRef<[u8]>>(input: T) -> Result<SmallVec<[u8; 32]>> {
+    decode_small_n(input)
+}
+
+/// decode_small_n() is like `decode_small()` but holds up to `N` bytes without allocating
+pub fn decode_small_n<const N: usize, T: AsRef<[u8]>>(input: T) -> Result<SmallVec<[u8; N]>> {
+    let indata = input.as_ref();
+    // Whitespace makes decoded_len() overestimate, so reserving it could spill output which
+    // would have fit
+    let mut outdata = SmallVec::new();
+    decode_groups(indata, &DecodeOptions::default(), |bytes| {
+        outdata.extend_from_slice(bytes);
+        Ok(())
+    })?;
+    Ok(outdata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode, encode_wrapped};
+
+    #[test]
+    fn test_decode_small() {
+        let data: Vec<u8> = (0..40).collect();
+        for len in [0, 31, 32, 33, 40] {
+            let decoded = decode_small(encode(&data[..len])).unwrap();
+            assert_eq!(decoded[..], data[..len]);
+            assert_eq!(decoded.spilled(), len > 32);
+        }
+
+        let decoded = decode_small_n::<4, _>("VPRom").unwrap();
+        assert!(!decoded.spilled());
+        assert_eq!(decoded[..], b"aaaa"[..]);
+        assert!(decode_small_n::<4, _>("VPRomVE").unwrap().spilled());
+        assert!(decode_small("VPRomV").is_err());
+
+        let spaced = encode_wrapped(&data[..32], 4);
+        assert!(!decode_small(spaced).unwrap().spilled());
+    }
+}
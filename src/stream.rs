@@ -0,0 +1,460 @@
+//! Adapters for encoding and decoding data as it passes through `std::io` readers and writers
+
+use std::io::{self, BufRead, Read, Write};
+
+use crate::{decoded_len, Base85Decoder, Base85Encoder, Error};
+
+// The number of groups encoded before they are handed to the inner writer
+const GROUPS_PER_WRITE: usize = 1024;
+
+/// encode_to_writer() encodes a slice of bytes straight into a writer, a few kilobytes at a time,
+/// instead of building the whole encoded String first
+pub fn encode_to_writer<W: Write>(indata: &[u8], w: &mut W) -> io::Result<()> {
+    let mut encoder = Encoder::new(w);
+    encoder.write_all(indata)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// encode_reader() reads everything from a reader and encodes it into one String. The input is
+/// read a few kilobytes at a time and encoded as it arrives, so it's never held in memory whole.
+pub fn encode_reader<R: Read>(mut r: R) -> io::Result<String> {
+    let mut inbuf = vec![0; READ_BUFFER_LEN];
+    let mut outdata = String::new();
+    let mut encoder = Base85Encoder::new();
+    loop {
+        let n = match r.read(&mut inbuf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        encoder.update_into(&inbuf[..n], &mut outdata);
+    }
+    outdata.push_str(&encoder.finalize());
+    Ok(outdata)
+}
+
+/// Encoder wraps a writer and encodes everything written to it before passing it along. Bytes
+/// are encoded in groups of 4, so the last 1 to 3 bytes are held back until `finish()` is called.
+/// Dropping an Encoder without calling `finish()` loses them.
+pub struct Encoder<W: Write> {
+    inner: W,
+    encoder: Base85Encoder,
+    // Encoded data which hasn't been written to the inner writer yet
+    outbuf: String,
+    out_pos: usize,
+}
+
+impl<W: Write> Encoder<W> {
+    /// new() creates an Encoder which writes encoded data to `inner`
+    pub fn new(inner: W) -> Encoder<W> {
+        Encoder {
+            inner,
+            encoder: Base85Encoder::new(),
+            outbuf: String::with_capacity(GROUPS_PER_WRITE * 5),
+            out_pos: 0,
+        }
+    }
+
+    /// finish() encodes any bytes still held back, exactly as `encode()` does for input which
+    /// isn't a multiple of 4 bytes long, and returns the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.drain()?;
+        self.encoder.finish_into(&mut self.outbuf);
+        self.drain()?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    // Writes out the encoded data in `outbuf`, keeping track of how much of it has gone so that
+    // none of it is written twice if the inner writer fails partway and the call is repeated
+    fn drain(&mut self) -> io::Result<()> {
+        while self.out_pos < self.outbuf.len() {
+            match self.inner.write(&self.outbuf.as_bytes()[self.out_pos..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.out_pos += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.outbuf.clear();
+        self.out_pos = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Encoded data from the last call has to go out before more is accepted
+        self.drain()?;
+
+        // Take no more than fits in one batch of groups
+        let len = buf.len().min(GROUPS_PER_WRITE * 4);
+        self.encoder.update_into(&buf[..len], &mut self.outbuf);
+
+        // Once the bytes are encoded they've been taken, so if writing them out fails here the
+        // error is left for the next call, which tries again
+        let _ = self.drain();
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain()?;
+        self.inner.flush()
+    }
+}
+
+// The number of bytes read from an inner reader at a time
+const READ_BUFFER_LEN: usize = 4096;
+
+/// Decoder wraps a reader of encoded data and yields the decoded bytes. Whitespace is skipped
+/// just as it is by `decode()`. Problems with the encoded data are reported as
+/// `io::ErrorKind::InvalidData` errors wrapping the crate's `Error`, except for data which ends
+/// partway through a group, which is an `io::ErrorKind::UnexpectedEof` error.
+pub struct Decoder<R: Read> {
+    inner: R,
+    decoder: Base85Decoder,
+    inbuf: Vec<u8>,
+    // Bytes decoded from the last read of the inner reader which haven't been handed over yet
+    outbuf: Vec<u8>,
+    out_pos: usize,
+    finished: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    /// new() creates a Decoder which reads encoded data from `inner`
+    pub fn new(inner: R) -> Decoder<R> {
+        Decoder {
+            inner,
+            decoder: Base85Decoder::new(),
+            inbuf: vec![0; READ_BUFFER_LEN],
+            outbuf: Vec::with_capacity(decoded_len(READ_BUFFER_LEN) + 4),
+            out_pos: 0,
+            finished: false,
+        }
+    }
+
+    /// into_inner() returns the inner reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Only go back to the inner reader once everything decoded so far has been handed over,
+        // and only until some bytes come of it, so that this call doesn't block needlessly
+        while self.out_pos == self.outbuf.len() {
+            if self.finished {
+                return Ok(0);
+            }
+
+            let n = match self.inner.read(&mut self.inbuf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.outbuf.clear();
+            self.out_pos = 0;
+            if n == 0 {
+                self.finished = true;
+                self.decoder.finish_into(&mut self.outbuf)?;
+            } else {
+                self.decoder
+                    .update_into(&self.inbuf[..n], &mut self.outbuf)?;
+            }
+        }
+
+        let n = (self.outbuf.len() - self.out_pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.outbuf[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
+
+/// decode_armored() decodes a block of encoded data wrapped in marker lines, like a PEM file.
+/// Lines up to and including the `begin` line and everything after the `end` line are ignored,
+/// as is whitespace around the markers and inside the block. Offsets in errors count from the
+/// start of the input. If the `begin` marker is missing, `Error::InvalidFrame` is returned with
+/// offset 0, and if the `end` marker is missing, with the offset of the `begin` line.
+pub fn decode_armored<R: BufRead>(mut reader: R, begin: &str, end: &str) -> io::Result<Vec<u8>> {
+    let mut line = String::new();
+    let mut offset = 0;
+    // Where the begin line starts, once it's been found
+    let mut block_start = 0;
+    let mut decoder: Option<Base85Decoder> = None;
+    let mut outdata = Vec::new();
+    loop {
+        line.clear();
+        let n = reader.read_line(&mut line)?;
+        if n == 0 {
+            return Err(Error::InvalidFrame {
+                offset: block_start,
+            }
+            .into());
+        }
+        offset += n;
+
+        match decoder.as_mut() {
+            None if line.trim() == begin => {
+                block_start = offset - n;
+                // The decoder counts offsets from the line after the marker
+                decoder = Some(Base85Decoder {
+                    offset,
+                    ..Base85Decoder::default()
+                });
+            }
+            None => {}
+            Some(_) if line.trim() == end => break,
+            Some(decoder) => outdata.extend(decoder.update(&line)?),
+        }
+    }
+
+    if let Some(decoder) = decoder {
+        outdata.extend(decoder.finalize()?);
+    }
+    Ok(outdata)
+}
+
+/// Errors become `io::ErrorKind::InvalidData` errors wrapping the original `Error`, except for
+/// the ones caused by the caller's arguments rather than the data, which become
+/// `io::ErrorKind::InvalidInput`, and data which was cut short, which becomes
+/// `io::ErrorKind::UnexpectedEof`
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        let kind = match e {
+            Error::UnexpectedEof { .. } => io::ErrorKind::UnexpectedEof,
+            Error::InvalidAlphabet(_) | Error::BufferTooSmall => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::io::{self, Read, Write};
+
+    // Hands out at most one byte per read() so that groups span calls to the inner reader
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    // Fails every other read() with `io::ErrorKind::Interrupted`, handing out the data in between
+    struct Interrupting<'a>(&'a [u8], bool);
+
+    impl Read for Interrupting<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.1 = !self.1;
+            if !self.1 {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_encoder() {
+        let data: Vec<u8> = (0..=255).cycle().take(10000).collect();
+
+        // Feed the data through in uneven pieces so groups straddle calls to write()
+        for &piece_len in [1, 2, 3, 5, 7, 4096].iter() {
+            let mut encoder = Encoder::new(Vec::new());
+            for piece in data.chunks(piece_len) {
+                encoder.write_all(piece).unwrap();
+            }
+            let encoded = encoder.finish().unwrap();
+            assert_eq!(encoded, encode(&data).into_bytes());
+        }
+
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(b"aaaaaaa").unwrap();
+        assert_eq!(encoder.finish().unwrap(), b"VPRomVPRn");
+    }
+
+    // Fails every other call to write() and writes no more than 3 bytes at a time otherwise
+    #[derive(Default)]
+    struct Flaky(Vec<u8>, bool);
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.1 = !self.1;
+            if self.1 {
+                return Err(io::ErrorKind::Other.into());
+            }
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encoder_write_errors() {
+        // Write errors are retried by calling again, which mustn't repeat or lose any output
+        let data: Vec<u8> = (0..=255).cycle().take(10000).collect();
+        let mut encoder = Encoder::new(Flaky::default());
+        let mut rest = &data[..];
+        let mut errors = 0;
+        while !rest.is_empty() {
+            match encoder.write(&rest[..rest.len().min(7)]) {
+                Ok(n) => rest = &rest[n..],
+                Err(_) => errors += 1,
+            }
+        }
+        while encoder.flush().is_err() {
+            errors += 1;
+        }
+        assert!(errors > 0);
+        assert_eq!(encoder.finish().unwrap().0, encode(&data).into_bytes());
+    }
+
+    #[test]
+    fn test_encode_to_writer() {
+        let data: Vec<u8> = (0..=255).cycle().take(20003).collect();
+        let mut encoded = Vec::new();
+        encode_to_writer(&data, &mut encoded).unwrap();
+        assert_eq!(encoded, encode(&data).into_bytes());
+    }
+
+    #[test]
+    fn test_decoder() {
+        let data: Vec<u8> = (0..=255).cycle().take(10000).collect();
+        let encoded = encode(&data);
+
+        let mut decoded = Vec::new();
+        Decoder::new(encoded.as_bytes())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+
+        let mut decoded = Vec::new();
+        Decoder::new(Trickle(b"VPR\nom VP\r\nRn\t"))
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"aaaaaaa");
+
+        let err = Decoder::new(Trickle(b"VPRo\"m"))
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(Error::InvalidCharacter {
+                byte: b'"',
+                offset: 4
+            })
+        ));
+
+        let err = Decoder::new(Trickle(b"VPRomV"))
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(matches!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(Error::UnexpectedEof {
+                offset: 6,
+                in_group: true
+            })
+        ));
+
+        // An interrupted read of the inner reader is retried rather than passed on
+        let mut decoded = Vec::new();
+        Decoder::new(Interrupting(b"VPRomVPRn", true))
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"aaaaaaa");
+    }
+
+    #[test]
+    fn test_encode_reader() {
+        let data: Vec<u8> = (0..=255).cycle().take(10003).collect();
+        let encoded = encode_reader(std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(encoded, encode(&data));
+        assert_eq!(encode_reader(Trickle(b"aaaaa")).unwrap(), "VPRomVE");
+    }
+
+    #[test]
+    fn test_decode_armored() {
+        let armored = "preamble\n-----BEGIN-----\r\nVPRom\n  VPRom\nVE\n-----END-----\ntrailer\n";
+        let decoded = decode_armored(armored.as_bytes(), "-----BEGIN-----", "-----END-----");
+        assert_eq!(decoded.unwrap(), b"aaaaaaaaa");
+
+        let err = decode_armored(
+            "-----BEGIN-----\nVPRom\n".as_bytes(),
+            "-----BEGIN-----",
+            "-----END-----",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(&Error::InvalidFrame { offset: 0 })
+        );
+        let err = decode_armored("VPRom\n".as_bytes(), "BEGIN", "END").unwrap_err();
+        assert_eq!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(&Error::InvalidFrame { offset: 0 })
+        );
+        let err = decode_armored("junk\nBEGIN\nVPRom\n".as_bytes(), "BEGIN", "END").unwrap_err();
+        assert_eq!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(&Error::InvalidFrame { offset: 5 })
+        );
+
+        // Offsets in errors count from the start of the input, not of the block
+        let err = decode_armored(
+            "junk\nBEGIN\nVPRom\nVP\"m\nEND\n".as_bytes(),
+            "BEGIN",
+            "END",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.into_inner().unwrap().downcast_ref::<Error>(),
+            Some(&Error::InvalidCharacter {
+                byte: b'"',
+                offset: 19
+            })
+        );
+    }
+
+    #[test]
+    fn test_io_error() {
+        fn decode_io(s: &str) -> io::Result<Vec<u8>> {
+            Ok(crate::decode(s)?)
+        }
+
+        assert_eq!(decode_io("VPRom").unwrap(), b"aaaa");
+        let err = decode_io("VPRo\"m").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            io::Error::from(Error::UnexpectedEof {
+                offset: 0,
+                in_group: false
+            })
+            .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            io::Error::from(Error::BufferTooSmall).kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
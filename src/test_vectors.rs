@@ -0,0 +1,66 @@
+//! Known-answer test vectors, for checking other implementations against this one.
+//!
+//! This is available only with the `test-vectors` feature. Every vector here is checked by the
+//! crate's own tests.
+
+/// IPV6_VECTORS pairs IPv6 addresses, as their 16 bytes in network order, with their encoding as
+/// one 128-bit number by `encode_ipv6()`. The first is the example from RFC 1924.
+pub const IPV6_VECTORS: &[([u8; 16], &str)] = &[
+    (
+        [
+            0x10, 0x80, 0, 0, 0, 0, 0, 0, 0, 0x08, 0x08, 0, 0x20, 0x0c, 0x41, 0x7a,
+        ],
+        "4)+k&C#VzJ4br>0wv%Yp",
+    ),
+    ([0; 16], "00000000000000000000"),
+    ([0xff; 16], "=r54lj&NUUO~Hi%c2ym0"),
+];
+
+/// VECTORS pairs data with its encoding by `encode()`, covering every length of partial group
+pub const VECTORS: &[(&[u8], &str)] = &[
+    (b"", ""),
+    (b"a", "VE"),
+    (b"aa", "VPO"),
+    (b"aaa", "VPRn"),
+    (b"aaaa", "VPRom"),
+    (b"aaaaa", "VPRomVE"),
+    (b"aaaaaa", "VPRomVPO"),
+    (b"aaaaaaa", "VPRomVPRn"),
+    (b"aaaaaaaa", "VPRomVPRom"),
+    (&[0], "00"),
+    (&[0; 4], "00000"),
+    (&[0; 5], "0000000"),
+    (&[0xff], "{{"),
+    (&[0xff; 4], "|NsC0"),
+    (&[0xff; 5], "|NsC0{{"),
+    (&[0xff; 16], "|NsC0|NsC0|NsC0|NsC0"),
+    (
+        &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+        "009C61O)~M2nh-c3=Iws",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, decode_ipv6, encode, encode_ipv6};
+    use core::net::Ipv6Addr;
+
+    #[test]
+    fn test_vectors() {
+        for &(data, encoded) in VECTORS {
+            assert_eq!(encode(data), encoded, "encoding {data:?}");
+            assert_eq!(decode(encoded).unwrap(), data, "decoding {encoded}");
+        }
+
+        for &(addr, encoded) in IPV6_VECTORS {
+            let addr = Ipv6Addr::from(addr);
+            assert_eq!(encode_ipv6(addr), encoded, "encoding {addr}");
+            assert_eq!(decode_ipv6(encoded).unwrap(), addr, "decoding {encoded}");
+        }
+        assert_eq!(
+            Ipv6Addr::from(IPV6_VECTORS[0].0),
+            "1080:0:0:0:8:800:200C:417A".parse::<Ipv6Addr>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! Bindings for using the crate from JavaScript through wasm-bindgen.
+//!
+//! This is available only with the `wasm` feature. Bytes go in and come out as `Uint8Array`s,
+//! and decoding errors are thrown as JavaScript `Error`s carrying the crate's error message.
+//! After building with `wasm-pack build --features wasm`:
+//!
+//! ```js
+//! import { encode, decode } from "base85";
+//!
+//! const encoded = encode(new TextEncoder().encode("hello"));
+//! const decoded = decode(encoded); // Uint8Array [104, 101, 108, 108, 111]
+//! ```
+
+use alloc::{string::String, vec::Vec};
+
+use wasm_bindgen::prelude::*;
+
+/// encode() turns a `Uint8Array` into a string of encoded data
+#[wasm_bindgen]
+pub fn encode(indata: &[u8]) -> String {
+    crate::encode(indata)
+}
+
+/// decode() turns a string of encoded data into a `Uint8Array`, throwing an `Error` if it can't
+/// be decoded
+#[wasm_bindgen]
+pub fn decode(instr: &str) -> Result<Vec<u8>, JsError> {
+    crate::decode(instr).map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_wasm() {
+        assert_eq!(encode(b"aaaaa"), "VPRomVE");
+        assert_eq!(decode("VPRomVE").unwrap(), b"aaaaa");
+        assert!(decode("VPRomV").is_err());
+    }
+}
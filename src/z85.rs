@@ -0,0 +1,52 @@
+//! The [Z85](https://rfc.zeromq.org/spec/32/) variant of Base85 used by ZeroMQ, most notably for
+//! CURVE keys.
+//!
+//! Z85 uses its own character set, the `Z85` alphabet, and only works with whole groups: the data
+//! to encode must be a multiple of 4 bytes long and the encoded string a multiple of 5 characters
+//! long. Whitespace is not allowed in encoded data.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{decode_blocks_with, encode_blocks_with, Result, Z85};
+
+/// encode() turns a slice of bytes into a string of Z85-encoded data. The input must be a
+/// multiple of 4 bytes long, otherwise `Error::InvalidLength` is returned.
+pub fn encode(indata: &[u8]) -> Result<String> {
+    encode_blocks_with(&Z85, indata)
+}
+
+/// decode() turns Z85-encoded data into a vector of bytes. The input must be a
+/// multiple of 5 characters long, otherwise `Error::InvalidLength` is returned.
+pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>> {
+    decode_blocks_with(&Z85, input.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_z85() {
+        // The test vector from the Z85 specification
+        let data = [0x86, 0x4F, 0xD2, 0x6F, 0xB5, 0x59, 0xF7, 0x5B];
+        assert_eq!(encode(&data).unwrap(), "HelloWorld");
+        assert_eq!(decode("HelloWorld").unwrap(), data);
+
+        // A CURVE public key from the ZeroMQ documentation
+        let key = "Yne@$w-vo<fVvi]a<NY6T1ed:M$fCG*[IaLV{hID";
+        let decoded = decode(key).unwrap();
+        assert_eq!(decoded.len(), 32);
+        assert_eq!(encode(&decoded).unwrap(), key);
+
+        assert!(matches!(encode(b"abc"), Err(Error::InvalidLength(3))));
+        assert!(matches!(
+            decode("Hello Worl"),
+            Err(Error::InvalidCharacter {
+                byte: b' ',
+                offset: 5
+            })
+        ));
+        assert!(matches!(decode("HelloWorl"), Err(Error::InvalidLength(9))));
+    }
+}